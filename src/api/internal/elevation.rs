@@ -353,16 +353,37 @@ impl Group {
             }
             GroupKind::Sub(i) => {
                 let (target, (constructor, params)) = data.map.get_index(i).ok_or_else(noerr)?;
+                let write_back = params.iter().any(|(k, v)| {
+                    matches!(k, OwnValue::Int(_))
+                        && v.as_value() == Value::Str(STD_ITP_PARAM_WRITE_BACK_ON_DROP)
+                });
+                // in a transaction, the writable elevations of a file share
+                // one domain, so that all their changes are written together
+                let shared_path = if transaction::is_active()
+                    && (write_back || data.origin.write_policy() != WritePolicy::ReadOnly)
+                {
+                    data.origin
+                        .read()
+                        .as_file_path()
+                        .ok()
+                        .map(|p| p.to_path_buf())
+                } else {
+                    None
+                };
+                if let Some(path) = &shared_path
+                    && let Some(root) = transaction::shared_elevation(path, target, params)
+                {
+                    return Ok(root);
+                }
+
                 // println!("construct elevation {} {:?}", target, params);
                 let cell = constructor(data.origin.clone(), target, params)?;
                 cell.set_self_as_domain_root();
-
-                for (k, v) in params.iter() {
-                    if matches!(k, OwnValue::Int(_))
-                        && v.as_value() == Value::Str(STD_ITP_PARAM_WRITE_BACK_ON_DROP)
-                    {
-                        cell.policy(WritePolicy::WriteBackOnDrop);
-                    }
+                if write_back {
+                    cell.write_back_on_drop();
+                }
+                if let Some(path) = shared_path {
+                    transaction::share_elevation(path, target, params.clone(), cell.clone());
                 }
                 Ok(cell)
             }
@@ -62,6 +62,10 @@ impl CellWriterTrait for HErr {
     fn set_value(&mut self, value: OwnValue) -> Res<()> {
         Err(self.clone())
     }
+
+    fn detach(&mut self) -> Res<()> {
+        Err(self.clone())
+    }
 }
 
 impl GroupTrait for HErr {
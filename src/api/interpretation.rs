@@ -1,4 +1,4 @@
-use std::{fmt::Debug, io::Read, marker::PhantomData};
+use std::{fmt::Debug, io::Read, marker::PhantomData, ops::Range};
 
 use crate::api::*;
 
@@ -44,8 +44,10 @@ pub trait CellReaderTrait: Debug {
 }
 
 pub trait CellWriterTrait: Debug {
+    /// changes the type of the cell, replacing its value or subtree
+    /// with an empty value of the new type
     fn set_ty(&mut self, new_type: &str) -> Res<()> {
-        todo!() // TODO: remove this default implementation
+        nores() // interpretations do not support changing cell types by default
     }
 
     fn set_index(&mut self, value: usize) -> Res<()> {
@@ -81,6 +83,13 @@ pub trait GroupTrait: Clone + Debug {
     fn at(&self, index: usize) -> Res<Self::Cell>;
     fn get_all(&self, label: Value<'_>) -> Res<Self::CellIterator>;
 
+    /// Returns the range of indices of the cells whose labels may start with
+    /// `prefix`, for groups which keep their cells sorted by label.
+    /// Used to search for label patterns without testing every cell.
+    fn label_prefix_range(&self, prefix: &str) -> Res<Range<usize>> {
+        nores() // groups are not sorted by label by default
+    }
+
     // creates a "detached" cell, use "add" to add it to the group
     fn create(&self, label: Option<OwnValue>, value: Option<OwnValue>) -> Res<Self::Cell> {
         nores() // interpretations do not support creating cells by default
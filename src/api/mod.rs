@@ -4,6 +4,7 @@ pub(crate) mod internal;
 pub mod interpretation;
 mod relation;
 mod selector;
+pub mod transaction;
 mod value;
 mod xell;
 
@@ -7,6 +7,117 @@ pub enum Selector<'a> {
     Str(&'a str),
     Star,
     DoubleStar,
+    /// `***`, like `**` but its filters are tested on the starting cell and
+    /// on every cell on the way down, descending only through matching cells
+    TripleStar,
+    /// Any of several comma-separated labels, e.g. `name,image` from `{name,image}`
+    Alt(&'a str),
+    /// A label pattern with `*`, `?` or `[abc]` wildcards, e.g. `*.yaml`
+    Glob(&'a str),
+}
+
+impl<'a> Selector<'a> {
+    /// The labels of an alternation selector, or the label of a string selector
+    pub fn alternatives(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        let s = match self {
+            Selector::Str(s) | Selector::Glob(s) => *s,
+            Selector::Alt(alts) => *alts,
+            Selector::Star | Selector::DoubleStar | Selector::TripleStar => "",
+        };
+        s.split(',').map(str::trim).filter(|s| !s.is_empty())
+    }
+}
+
+/// Returns true if the label contains glob wildcards
+pub fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// The literal part of a glob pattern, before the first wildcard
+pub fn glob_prefix(pattern: &str) -> &str {
+    &pattern[..pattern.find(['*', '?', '[']).unwrap_or(pattern.len())]
+}
+
+/// Matches a label against a glob pattern: `*` matches any sequence of
+/// characters, `?` matches one character and `[abc]`, `[a-z]` or `[!abc]`
+/// match one character of (or not of) a set.
+pub fn glob_match(pattern: &str, label: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let label: Vec<char> = label.chars().collect();
+    // position in pattern after the last `*` and position in label it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut l) = (0, 0);
+    while l < label.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, l));
+                p += 1;
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                l += 1;
+                continue;
+            }
+            Some('[') => {
+                if let Some((matched, next)) = match_class(&pattern, p, label[l]) {
+                    if matched {
+                        p = next;
+                        l += 1;
+                        continue;
+                    }
+                } else if label[l] == '[' {
+                    // unclosed class, a literal `[`
+                    p += 1;
+                    l += 1;
+                    continue;
+                }
+            }
+            Some(c) if *c == label[l] => {
+                p += 1;
+                l += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // mismatch: let the last `*` consume one more character
+        match star {
+            Some((star_p, star_l)) => {
+                star = Some((star_p, star_l + 1));
+                p = star_p;
+                l = star_l + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Matches a character against the class starting at `pattern[start] == '['`.
+/// Returns whether it matched and the position after the class, or None if
+/// the class is not closed.
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let x = *pattern.get(i)?;
+        if x == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|y| *y != ']') {
+            matched |= x <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= x == c;
+            i += 1;
+        }
+    }
 }
 
 impl<'a> From<&'a str> for Selector<'a> {
@@ -15,6 +126,10 @@ impl<'a> From<&'a str> for Selector<'a> {
             Selector::Star
         } else if s == "**" {
             Selector::DoubleStar
+        } else if s == "***" {
+            Selector::TripleStar
+        } else if is_glob(s) {
+            Selector::Glob(s)
         } else {
             Selector::Str(s)
         }
@@ -33,15 +148,20 @@ where
 {
     fn eq(&self, other: &T) -> bool {
         match self {
-            Selector::Star | Selector::DoubleStar => true,
+            Selector::Star | Selector::DoubleStar | Selector::TripleStar => true,
             Selector::Str(s) => s.eq(&other.borrow()),
+            Selector::Alt(_) => self.alternatives().any(|s| s.eq(other.borrow())),
+            Selector::Glob(pattern) => glob_match(pattern, other.borrow()),
         }
     }
 }
 
 impl PartialEq<Value<'_>> for Selector<'_> {
     fn eq(&self, other: &Value) -> bool {
-        if *self == Selector::Star || *self == Selector::DoubleStar {
+        if matches!(
+            self,
+            Selector::Star | Selector::DoubleStar | Selector::TripleStar
+        ) {
             return true;
         }
         match other {
@@ -57,6 +177,12 @@ impl PartialEq<Selector<'_>> for Value<'_> {
             Selector::Str(svalue) => self.eq(svalue),
             Selector::Star => true,
             Selector::DoubleStar => true,
+            Selector::TripleStar => true,
+            Selector::Alt(_) => other.alternatives().any(|s| self.eq(&s)),
+            Selector::Glob(pattern) => match self {
+                Value::Str(s) => glob_match(pattern, s),
+                _ => false,
+            },
         }
     }
 }
@@ -65,8 +191,11 @@ impl<'a> Display for Selector<'a> {
     fn fmt(&self, buf: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Selector::DoubleStar => write!(buf, "**"),
+            Selector::TripleStar => write!(buf, "***"),
             Selector::Star => write!(buf, "*"),
             Selector::Str(x) => write!(buf, "{}", x),
+            Selector::Alt(x) => write!(buf, "{{{}}}", x),
+            Selector::Glob(x) => write!(buf, "{}", x),
         }
     }
 }
@@ -77,6 +206,12 @@ impl PartialEq<Selector<'_>> for OwnValue {
             Selector::Str(svalue) => self.eq(svalue),
             Selector::Star => true,
             Selector::DoubleStar => true,
+            Selector::TripleStar => true,
+            Selector::Alt(_) => other.alternatives().any(|s| self.eq(&s)),
+            Selector::Glob(pattern) => match self {
+                OwnValue::String(s) => glob_match(pattern, s),
+                _ => false,
+            },
         }
     }
 }
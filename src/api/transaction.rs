@@ -0,0 +1,428 @@
+use std::{
+    cell::RefCell,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+use crate::{api::*, warning};
+
+// While a transaction is active, domains which would be written back on drop
+// are created with `WritePolicy::NoAutoWrite`; the ones which become dirty are
+// kept here and written together when the transaction is committed. All the
+// writable elevations of a file share one domain, so the statements of a
+// program see the changes of the previous ones. Direct writes to files are
+// kept here as well, and all the writes are committed in the order of the
+// statements which made them.
+thread_local! {
+    static SCOPE: RefCell<Option<Scope>> = const { RefCell::new(None) };
+}
+
+struct Scope {
+    dry_run: bool,
+    // the dirty domains, with the order in which they must be written
+    dirty_domains: Vec<(Rc<Domain>, usize)>,
+    file_writes: Vec<FileWrite>,
+    next_order: usize,
+    // the domains which are written back on drop again after the commit
+    deferred: Vec<Weak<Domain>>,
+    elevations: Vec<SharedElevation>,
+}
+
+// a direct write to the content of a file
+struct FileWrite {
+    order: usize,
+    path: PathBuf,
+    write: PendingWrite,
+}
+
+struct SharedElevation {
+    path: PathBuf,
+    interpretation: &'static str,
+    params: ElevateParams,
+    root: Xell,
+}
+
+/// A write which is done on a file when the transaction is committed.
+#[derive(Clone, Debug)]
+pub struct PendingWrite {
+    /// the file cell which is written
+    pub target: Xell,
+    /// the interpretation of the modified domain, e.g. `json`
+    pub interpretation: String,
+    /// the new serialisation of the modified domain
+    pub serial: String,
+}
+
+impl PendingWrite {
+    pub fn file_path(&self) -> Res<PathBuf> {
+        Ok(self.target.read().as_file_path()?.to_path_buf())
+    }
+
+    /// Diffs the current content of the target against the new serialisation,
+    /// both seen through the interpretation of the modified domain.
+    pub fn diff(&self) -> Res<Xell> {
+        let original = self.target.be(&self.interpretation).err()?;
+        let modified = Xell::from(self.serial.as_str())
+            .be(&self.interpretation)
+            .err()?;
+        original.diff(&modified)
+    }
+}
+
+/// A file or an empty directory which is removed when the transaction is
+/// committed, or when its domain is saved.
+#[derive(Clone, Debug)]
+pub struct PendingRemoval {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+impl PendingRemoval {
+    pub(crate) fn apply(&self) -> Res<()> {
+        let removed = if self.is_dir {
+            std::fs::remove_dir(&self.path)
+        } else {
+            std::fs::remove_file(&self.path)
+        };
+        removed.map_err(|e| caused(HErrKind::IO, format!("cannot delete: {:?}", self.path), e))
+    }
+}
+
+/// All the changes of a transaction, which are applied together on commit.
+#[derive(Clone, Debug, Default)]
+pub struct Changes {
+    pub writes: Vec<PendingWrite>,
+    pub removals: Vec<PendingRemoval>,
+    deferred: Vec<Weak<Domain>>,
+}
+
+// the state of a path before the commit touched it, used to restore it
+enum Original {
+    File(Vec<u8>),
+    Dir,
+    Missing,
+}
+
+/// Starts a transaction on the current thread. A dry run is never committed,
+/// its changes are only previewed.
+pub fn begin(dry_run: bool) -> Res<()> {
+    SCOPE.with(|s| {
+        let mut scope = s.borrow_mut();
+        if scope.is_some() {
+            return fault("a transaction is already active");
+        }
+        *scope = Some(Scope {
+            dry_run,
+            dirty_domains: vec![],
+            file_writes: vec![],
+            next_order: 0,
+            deferred: vec![],
+            elevations: vec![],
+        });
+        Ok(())
+    })
+}
+
+pub fn is_active() -> bool {
+    SCOPE.try_with(|s| s.borrow().is_some()).unwrap_or(false)
+}
+
+pub fn is_dry_run() -> bool {
+    SCOPE
+        .try_with(|s| s.borrow().as_ref().is_some_and(|s| s.dry_run))
+        .unwrap_or(false)
+}
+
+/// Ends the transaction and drops all its changes.
+pub fn abort() {
+    let _ = SCOPE.try_with(|s| s.borrow_mut().take());
+}
+
+/// Ends the transaction and serialises all its changes, returning the file
+/// writes and removals which must be committed. Elevations of in-memory cells
+/// are saved into their origin first, innermost domains first.
+pub fn finish() -> Res<Changes> {
+    let result = collect_changes();
+    let scope = SCOPE.with(|s| s.borrow_mut().take());
+    let mut changes = result?;
+    changes.deferred = scope.map(|s| s.deferred).unwrap_or_default();
+    Ok(changes)
+}
+
+fn collect_changes() -> Res<Changes> {
+    let mut done: Vec<Rc<Domain>> = vec![];
+    let mut writes: Vec<(usize, PendingWrite)> = vec![];
+    let mut removals = vec![];
+    loop {
+        // saving a domain into its origin makes the origin domain dirty, so the
+        // list can grow while it is processed
+        let next = SCOPE.with(|s| {
+            let scope = s.borrow();
+            let domains = scope.as_ref().map_or(&[][..], |s| &s.dirty_domains[..]);
+            domains
+                .iter()
+                .filter(|(d, _)| !done.iter().any(|x| Rc::ptr_eq(x, d)))
+                .max_by_key(|(d, _)| d.depth())
+                .cloned()
+        });
+        let Some((domain, order)) = next else {
+            break;
+        };
+        done.push(Rc::clone(&domain));
+        removals.extend(domain.take_removals());
+        let Some(write) = domain.pending_write()? else {
+            continue;
+        };
+        if write.target.interpretation() != "fs" {
+            write.target.write().value(OwnValue::String(write.serial))?;
+            // the origin is written in the place of this domain
+            if let Some(origin) = domain.origin_domain() {
+                reorder(&origin, order);
+            }
+            continue;
+        }
+        let path = write.file_path()?;
+        for (_, other) in &writes {
+            if other.file_path()? == path {
+                return inputres(format!(
+                    "{} is modified through elevations with different interpretations or parameters",
+                    path.display()
+                ));
+            }
+        }
+        writes.push((order, write));
+    }
+    let file_writes = SCOPE.with(|s| {
+        s.borrow_mut()
+            .as_mut()
+            .map(|s| std::mem::take(&mut s.file_writes))
+            .unwrap_or_default()
+    });
+    writes.extend(file_writes.into_iter().map(|fw| (fw.order, fw.write)));
+    writes.sort_by_key(|(order, _)| *order);
+    Ok(Changes {
+        writes: writes.into_iter().map(|(_, write)| write).collect(),
+        removals,
+        deferred: vec![],
+    })
+}
+
+// moves the domain before the later writes, if it is not already there
+fn reorder(domain: &Rc<Domain>, order: usize) {
+    SCOPE.with(|s| {
+        if let Some(scope) = s.borrow_mut().as_mut()
+            && let Some(entry) = scope
+                .dirty_domains
+                .iter_mut()
+                .find(|(d, _)| Rc::ptr_eq(d, domain))
+        {
+            entry.1 = entry.1.min(order);
+        }
+    });
+}
+
+/// Writes all files, each one to a temporary file which is then renamed over
+/// the original, then removes the deleted files and directories. If anything
+/// fails, the files already written or removed are restored. Otherwise the
+/// domains of the transaction are written back on drop again, so later writes
+/// through them are not lost.
+pub fn commit(changes: &Changes) -> Res<()> {
+    let mut done: Vec<(PathBuf, Original)> = vec![];
+    for write in &changes.writes {
+        let result = write.file_path().and_then(|path| {
+            let original = read_original(&path)?;
+            write_atomically(&path, write.serial.as_bytes())?;
+            done.push((path, original));
+            Ok(())
+        });
+        if let Err(err) = result {
+            rollback(&done);
+            return Err(err);
+        }
+    }
+    for removal in &changes.removals {
+        let result = read_original(&removal.path).and_then(|original| {
+            removal.apply()?;
+            done.push((removal.path.clone(), original));
+            Ok(())
+        });
+        if let Err(err) = result {
+            rollback(&done);
+            return Err(err);
+        }
+    }
+    for domain in changes.deferred.iter().filter_map(Weak::upgrade) {
+        domain.end_deferral();
+    }
+    Ok(())
+}
+
+fn read_original(path: &Path) -> Res<Original> {
+    let io_err = |e| caused(HErrKind::IO, format!("cannot read file: {:?}", path), e);
+    match std::fs::metadata(path) {
+        Ok(md) if md.is_dir() => Ok(Original::Dir),
+        Ok(_) => std::fs::read(path).map(Original::File).map_err(io_err),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Original::Missing),
+        Err(e) => Err(io_err(e)),
+    }
+}
+
+fn rollback(done: &[(PathBuf, Original)]) {
+    for (path, original) in done.iter().rev() {
+        let result = match original {
+            Original::File(content) => write_atomically(path, content),
+            Original::Dir => std::fs::create_dir(path)
+                .map_err(|e| caused(HErrKind::IO, format!("cannot create {:?}", path), e)),
+            Original::Missing => std::fs::remove_file(path)
+                .map_err(|e| caused(HErrKind::IO, format!("cannot remove {:?}", path), e)),
+        };
+        if let Err(err) = result {
+            warning!("💥 while restoring {:?}: {:?}", path, err);
+        }
+    }
+}
+
+fn write_atomically(path: &Path, content: &[u8]) -> Res<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| inputerr(format!("cannot write to {:?}, not a file", path)))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".hial-{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let io_err =
+        |e: std::io::Error| caused(HErrKind::IO, format!("cannot write to file: {:?}", path), e);
+    std::fs::write(&temp_path, content).map_err(io_err)?;
+    let result = std::fs::metadata(path)
+        .and_then(|md| std::fs::set_permissions(&temp_path, md.permissions()))
+        .or_else(|e| match e.kind() {
+            ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(io_err(e));
+    }
+    Ok(())
+}
+
+pub(crate) fn register(domain: &Rc<Domain>) {
+    let _ = SCOPE.try_with(|s| {
+        if let Some(scope) = s.borrow_mut().as_mut()
+            && !scope
+                .dirty_domains
+                .iter()
+                .any(|(x, _)| Rc::ptr_eq(x, domain))
+        {
+            scope
+                .dirty_domains
+                .push((Rc::clone(domain), scope.next_order));
+            scope.next_order += 1;
+        }
+    });
+}
+
+/// Keeps a direct write to the content of a file until the commit. Later
+/// elevations of the file see the new content.
+pub(crate) fn write_file(path: PathBuf, write: PendingWrite) -> Res<()> {
+    let stale = SCOPE.with(|s| {
+        let mut scope = s.borrow_mut();
+        let scope = scope
+            .as_mut()
+            .ok_or_else(|| faulterr("no active transaction"))?;
+        scope.file_writes.push(FileWrite {
+            order: scope.next_order,
+            path: path.clone(),
+            write,
+        });
+        scope.next_order += 1;
+        // the elevations made before this write see the old content
+        let (stale, shared) = std::mem::take(&mut scope.elevations)
+            .into_iter()
+            .partition(|e| e.path == path);
+        scope.elevations = shared;
+        Ok::<Vec<SharedElevation>, HErr>(stale)
+    })?;
+    drop(stale);
+    Ok(())
+}
+
+/// The content of the last direct write to a file, if any.
+pub(crate) fn pending_content(path: &Path) -> Option<String> {
+    SCOPE
+        .try_with(|s| {
+            s.borrow().as_ref().and_then(|s| {
+                s.file_writes
+                    .iter()
+                    .rev()
+                    .find(|fw| fw.path == path)
+                    .map(|fw| fw.write.serial.clone())
+            })
+        })
+        .ok()
+        .flatten()
+}
+
+// the domain is kept in memory until the commit, instead of being written
+// back on drop
+pub(crate) fn defer(domain: &Rc<Domain>) {
+    let _ = SCOPE.try_with(|s| {
+        if let Some(scope) = s.borrow_mut().as_mut() {
+            scope.deferred.push(Rc::downgrade(domain));
+        }
+    });
+}
+
+pub(crate) fn is_deferred(domain: &Rc<Domain>) -> bool {
+    SCOPE
+        .try_with(|s| {
+            s.borrow().as_ref().is_some_and(|s| {
+                s.deferred
+                    .iter()
+                    .any(|x| std::ptr::eq(x.as_ptr(), Rc::as_ptr(domain)))
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// The root of an earlier writable elevation of the same file, if any.
+pub(crate) fn shared_elevation(
+    path: &Path,
+    interpretation: &str,
+    params: &ElevateParams,
+) -> Option<Xell> {
+    SCOPE
+        .try_with(|s| {
+            s.borrow().as_ref().and_then(|s| {
+                s.elevations
+                    .iter()
+                    .find(|e| {
+                        e.path == path && e.interpretation == interpretation && &e.params == params
+                    })
+                    .map(|e| e.root.clone())
+            })
+        })
+        .ok()
+        .flatten()
+}
+
+pub(crate) fn share_elevation(
+    path: PathBuf,
+    interpretation: &'static str,
+    params: ElevateParams,
+    root: Xell,
+) {
+    let _ = SCOPE.try_with(|s| {
+        if let Some(scope) = s.borrow_mut().as_mut() {
+            scope.elevations.push(SharedElevation {
+                path,
+                interpretation,
+                params,
+                root,
+            });
+        }
+    });
+}
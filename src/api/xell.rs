@@ -2,6 +2,7 @@ use std::{
     cell::{self, OnceCell},
     fmt::{self, Debug, Write},
     io::Read,
+    ops::Range,
     rc::Rc,
 };
 
@@ -28,6 +29,8 @@ pub struct Domain {
     origin: Option<Xell>,
     pub(super) dyn_root: OnceCell<DynCell>,
     dirty: cell::Cell<bool>,
+    // files deleted from this domain, removed from disk when it is saved
+    removals: cell::RefCell<Vec<transaction::PendingRemoval>>,
 }
 
 enumerated_dynamic_type! {
@@ -113,6 +116,8 @@ pub struct CellWriter {
     dyn_cell_writer: DynCellWriter,
     // keep it in a rc to avoid other allocations
     domain: Rc<Domain>,
+    // a file cell written in a transaction, its content is written on commit
+    file: Option<Xell>,
 }
 
 enumerated_dynamic_type! {
@@ -239,6 +244,7 @@ impl From<HErr> for Xell {
                 origin: None,
                 dyn_root: OnceCell::new(),
                 dirty: cell::Cell::new(false),
+                removals: cell::RefCell::new(vec![]),
             }),
         }
     }
@@ -286,30 +292,47 @@ impl CellReader {
 impl CellWriter {
     pub fn label(&mut self, value: impl Into<OwnValue>) -> Res<()> {
         let value = value.into();
-        self.domain.dirty.set(true);
+        self.domain.set_dirty();
         dispatch_dyn_cell_writer!(&mut self.dyn_cell_writer, |x| { x.set_label(value) })
     }
 
     pub fn value(&mut self, value: impl Into<OwnValue>) -> Res<()> {
         let value = value.into();
-        self.domain.dirty.set(true);
+        self.domain.set_dirty();
+        if let (Some(file), DynCellWriter::File(writer)) = (&self.file, &self.dyn_cell_writer) {
+            let (path, write) = writer.pending_write(file.clone(), value)?;
+            return transaction::write_file(path, write);
+        }
         dispatch_dyn_cell_writer!(&mut self.dyn_cell_writer, |x| { x.set_value(value) })
     }
 
     pub fn index(&mut self, index: usize) -> Res<()> {
-        self.domain.dirty.set(true);
+        self.domain.set_dirty();
         dispatch_dyn_cell_writer!(&mut self.dyn_cell_writer, |x| { x.set_index(index) })
     }
 
     pub fn ty(&mut self, ty: &str) -> Res<()> {
-        self.domain.dirty.set(true);
+        self.domain.set_dirty();
         dispatch_dyn_cell_writer!(&mut self.dyn_cell_writer, |x| { x.set_ty(ty) })
     }
 
     pub fn serial(&mut self, serial: OwnValue) -> Res<()> {
-        self.domain.dirty.set(true);
+        self.domain.set_dirty();
         dispatch_dyn_cell_writer!(&mut self.dyn_cell_writer, |x| { x.set_serial(serial) })
     }
+
+    /// Removes the cell from its group. Files are removed from disk only
+    /// when the domain is saved.
+    pub fn detach(&mut self) -> Res<()> {
+        self.domain.set_dirty();
+        if let DynCellWriter::File(writer) = &mut self.dyn_cell_writer {
+            let removal = writer.removal()?;
+            writer.detach()?;
+            self.domain.removals.borrow_mut().push(removal);
+            return Ok(());
+        }
+        dispatch_dyn_cell_writer!(&mut self.dyn_cell_writer, |x| { x.detach() })
+    }
 }
 impl CellWriter {
     pub fn err(self) -> Res<CellWriter> {
@@ -345,14 +368,34 @@ impl Xell {
         let wp = origin
             .as_ref()
             .map_or(WritePolicy::ReadOnly, |c| c.domain.write_policy.get());
-        Xell {
-            dyn_cell,
-            domain: Rc::new(Domain {
-                write_policy: cell::Cell::new(wp),
-                origin,
-                dyn_root: OnceCell::new(),
-                dirty: cell::Cell::new(false),
-            }),
+        let domain = Rc::new(Domain {
+            write_policy: cell::Cell::new(wp),
+            origin,
+            dyn_root: OnceCell::new(),
+            dirty: cell::Cell::new(false),
+            removals: cell::RefCell::new(vec![]),
+        });
+        // the policy is inherited, and so is its deferral by a transaction
+        if let Some(origin) = &domain.origin
+            && transaction::is_deferred(&origin.domain)
+        {
+            transaction::defer(&domain);
+        }
+        Xell { dyn_cell, domain }
+    }
+
+    pub(crate) fn write_policy(&self) -> WritePolicy {
+        self.domain.write_policy.get()
+    }
+
+    /// Makes the domain write back its changes on drop. In a transaction, the
+    /// changes are kept in memory until the transaction is committed.
+    pub(crate) fn write_back_on_drop(&self) {
+        if transaction::is_active() {
+            self.domain.write_policy.set(WritePolicy::NoAutoWrite);
+            transaction::defer(&self.domain);
+        } else {
+            self.domain.write_policy.set(WritePolicy::WriteBackOnDrop);
         }
     }
 
@@ -405,18 +448,21 @@ impl Xell {
             return CellWriter {
                 dyn_cell_writer: DynCellWriter::from(err),
                 domain: Rc::clone(&self.domain),
+                file: None,
             };
         }
-
         let writer: DynCellWriter = dispatch_dyn_cell!(&self.dyn_cell, |x| {
             match x.write() {
                 Ok(r) => DynCellWriter::from(r),
                 Err(e) => DynCellWriter::from(e.with_xell(self.clone())),
             }
         });
+        let file = (matches!(self.dyn_cell, DynCell::File(_)) && transaction::is_active())
+            .then(|| self.clone());
         CellWriter {
             dyn_cell_writer: writer,
             domain: Rc::clone(&self.domain),
+            file,
         }
     }
 
@@ -568,6 +614,36 @@ impl Xell {
         })
     }
 
+    /// Returns the parent cell: the head cell or, if this cell is the root of
+    /// its domain, the origin cell which was interpreted into this domain.
+    pub fn parent(&self) -> Res<Xell> {
+        match self.head() {
+            Ok((cell, _)) => Ok(cell),
+            Err(e) if e.kind == HErrKind::None => self.domain.origin.clone().ok_or_else(noerr),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns all the ancestors of this cell, nearest first, crossing domain
+    /// boundaries back to the first origin. The current cell is not included.
+    pub fn ancestors(&self) -> Res<Vec<Xell>> {
+        let mut v = vec![];
+        let mut parent = self.parent();
+        while let Ok(p) = parent {
+            parent = p.parent();
+            v.push(p);
+            if v.len() > MAX_PATH_ITEMS {
+                return fault("ancestor iteration limit reached");
+            }
+        }
+        let err = parent.unwrap_err();
+        if err.kind == HErrKind::None {
+            Ok(v)
+        } else {
+            Err(err)
+        }
+    }
+
     /// Returns the path of head cells and relations in the current domain.
     /// The current cell is not included. If the path is empty, the current
     /// cell is the domain root. HErrKind::None is never returned.
@@ -786,24 +862,29 @@ impl Xell {
         let dyn_root = guard_some!(self.domain.dyn_root.get(), {
             return fault("domain root not found while saving domain");
         });
-        Self::save_from_to(dyn_root, target)
+        Self::save_from_to(dyn_root, target)?;
+        for removal in self.domain.take_removals() {
+            removal.apply()?;
+        }
+        Ok(())
     }
 
     fn save_from_to(dyn_cell: &DynCell, target: &Xell) -> Res<()> {
-        let serial = dispatch_dyn_cell!(dyn_cell, |x| {
-            match x.read()?.serial() {
-                Ok(serial) => serial,
-                Err(e) => {
-                    if e.kind == HErrKind::None {
-                        return Ok(()); // no serial, nothing to save
-                    } else {
-                        return Err(e);
-                    }
-                }
-            }
+        let serial = guard_some!(Self::serial_of(dyn_cell)?, {
+            return Ok(()); // no serial, nothing to save
         });
         target.write().value(OwnValue::String(serial))
     }
+
+    fn serial_of(dyn_cell: &DynCell) -> Res<Option<String>> {
+        dispatch_dyn_cell!(dyn_cell, |x| {
+            match x.read()?.serial() {
+                Ok(serial) => Ok(Some(serial)),
+                Err(e) if e.kind == HErrKind::None => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+    }
 }
 
 impl fmt::Debug for Xell {
@@ -838,6 +919,47 @@ impl fmt::Debug for Domain {
     }
 }
 
+impl Domain {
+    fn set_dirty(self: &Rc<Self>) {
+        self.dirty.set(true);
+        if self.write_policy.get() == WritePolicy::NoAutoWrite && transaction::is_active() {
+            transaction::register(self);
+        }
+    }
+
+    // the number of domains in the origin chain
+    pub(super) fn depth(&self) -> usize {
+        self.origin.as_ref().map_or(0, |o| o.domain.depth() + 1)
+    }
+
+    pub(super) fn origin_domain(&self) -> Option<Rc<Domain>> {
+        self.origin.as_ref().map(|o| Rc::clone(&o.domain))
+    }
+
+    // the transaction which kept the changes in memory has written them
+    pub(super) fn end_deferral(&self) {
+        self.dirty.set(false);
+        self.write_policy.set(WritePolicy::WriteBackOnDrop);
+    }
+
+    pub(super) fn take_removals(&self) -> Vec<transaction::PendingRemoval> {
+        self.removals.take()
+    }
+
+    pub(super) fn pending_write(&self) -> Res<Option<transaction::PendingWrite>> {
+        let (Some(target), Some(dyn_root)) = (self.origin.as_ref(), self.dyn_root.get()) else {
+            return Ok(None);
+        };
+        let serial = guard_some!(Xell::serial_of(dyn_root)?, { return Ok(None) });
+        let interpretation = dispatch_dyn_cell!(dyn_root, |x| { x.interpretation() });
+        Ok(Some(transaction::PendingWrite {
+            target: target.clone(),
+            interpretation: interpretation.to_string(),
+            serial,
+        }))
+    }
+}
+
 impl Drop for Domain {
     fn drop(&mut self) {
         if self.write_policy.get() != WritePolicy::WriteBackOnDrop {
@@ -855,6 +977,11 @@ impl Drop for Domain {
         if let Err(err) = Xell::save_from_to(dyn_root, target) {
             warning!("💥 while trying to auto-save domain: {:?}", err);
         }
+        for removal in self.take_removals() {
+            if let Err(err) = removal.apply() {
+                warning!("💥 while trying to auto-save domain: {:?}", err);
+            }
+        }
     }
 }
 
@@ -887,6 +1014,10 @@ impl Group {
         self.len().is_ok_and(|l| l == 0)
     }
 
+    pub fn label_prefix_range(&self, prefix: &str) -> Res<Range<usize>> {
+        dispatch_dyn_group!(&self.dyn_group, |x| { x.label_prefix_range(prefix) })
+    }
+
     pub fn at(&self, index: usize) -> Xell {
         if let DynGroup::Elevation(group) = &self.dyn_group {
             // special case for elevation group
@@ -969,6 +1100,13 @@ impl Group {
     }
 
     pub fn add(&self, index: Option<usize>, cell: Xell) -> Res<()> {
+        if !matches!(self.dyn_group, DynGroup::Elevation(_)) {
+            // elevation groups are written to set elevation parameters
+            if self.domain.write_policy.get() == WritePolicy::ReadOnly {
+                return inputres("cannot add cell, read-only domain");
+            }
+            self.domain.set_dirty();
+        }
         dispatch_dyn_group!(&self.dyn_group, |x| { x.add(index, cell.try_into()?) })
     }
 
@@ -1037,6 +1175,7 @@ impl Iterator for CellIterator {
                                 origin: None,
                                 dyn_root: OnceCell::new(),
                                 dirty: cell::Cell::new(false),
+                                removals: cell::RefCell::new(vec![]),
                             }),
                         })
                     }
@@ -1077,6 +1216,7 @@ impl DoubleEndedIterator for CellIterator {
                                 origin: None,
                                 dyn_root: OnceCell::new(),
                                 dirty: cell::Cell::new(false),
+                                removals: cell::RefCell::new(vec![]),
                             }),
                         })
                     }
@@ -1,4 +1,4 @@
-use crate::api::*;
+use crate::{api::*, prog::Program};
 use serde::Deserialize;
 use std::{fs, io::ErrorKind, path::PathBuf};
 
@@ -53,7 +53,7 @@ pub fn load_main_config() -> Res<MainConfig> {
 pub fn load_prelude_text() -> Res<Option<String>> {
     let path = config_dir()?.join(PRELUDE_FILE);
     match fs::read_to_string(&path) {
-        Ok(contents) => Ok(Some(contents)),
+        Ok(contents) => Ok(Some(Program::strip_comments(&contents))),
         Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
         Err(err) => Err(caused(
             HErrKind::IO,
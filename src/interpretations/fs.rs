@@ -1,10 +1,12 @@
 use std::{
     borrow::Cow,
-    cell::OnceCell,
+    cell::{OnceCell, RefCell},
     cmp::Ordering,
     ffi::OsString,
     fs,
+    ops::Range,
     path::{Path, PathBuf},
+    rc::Rc,
 };
 
 use indexmap::{IndexMap, indexmap};
@@ -54,6 +56,8 @@ pub(crate) struct Group {
 pub(crate) struct FileList {
     list: IndexMap<String, Res<FileEntry>>,
     full: bool, // if true, all files from parent folder are present in the list
+    // files deleted in this domain, hidden from listings until they are removed
+    deleted: Rc<RefCell<Vec<PathBuf>>>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -148,9 +152,13 @@ impl CellReaderTrait for CellReader {
                 }
                 // TODO: stream here instead of reading the whole file into memory
                 if self.cached_value.get().is_none() {
-                    let content = std::fs::read(&fe.path).map_err(|e| {
-                        caused(HErrKind::IO, format!("cannot read file: {:?}", fe.path), e)
-                    })?;
+                    // in a transaction, the file has the content of its last write
+                    let content = match transaction::pending_content(&fe.path) {
+                        Some(content) => content.into_bytes(),
+                        None => std::fs::read(&fe.path).map_err(|e| {
+                            caused(HErrKind::IO, format!("cannot read file: {:?}", fe.path), e)
+                        })?,
+                    };
                     self.cached_value
                         .set(content.into_boxed_slice())
                         .map_err(|_| faulterr("cannot set cached value, it is already set"))?;
@@ -242,9 +250,67 @@ impl CellWriterTrait for CellWriter {
             }
         }
     }
+
+    // the file is only removed from the list, it is removed from disk when the
+    // domain is saved (see `removal`)
+    fn detach(&mut self) -> Res<()> {
+        let GroupType::Folder = self.ty else {
+            return inputres("cannot delete file attributes");
+        };
+        let path = self.fileentry()?.path.clone();
+        self.files.deleted.borrow_mut().push(path);
+        self.files.list.shift_remove_index(self.pos as usize);
+        Ok(())
+    }
 }
 
 impl CellWriter {
+    /// The removal of this file from disk, to be done when the domain is saved.
+    pub(crate) fn removal(&self) -> Res<transaction::PendingRemoval> {
+        let GroupType::Folder = self.ty else {
+            return inputres("cannot delete file attributes");
+        };
+        let fe = self.fileentry()?;
+        let md = fe.metadata.as_ref().map_err(|e| e.clone())?;
+        // only empty directories are removed, to avoid deleting whole trees by accident
+        if md.is_dir {
+            let mut entries = fs::read_dir(&fe.path)
+                .map_err(|e| caused(HErrKind::IO, format!("cannot read dir: {:?}", fe.path), e))?;
+            if entries.next().is_some() {
+                return inputres(format!("cannot delete non-empty directory: {:?}", fe.path));
+            }
+        }
+        Ok(transaction::PendingRemoval {
+            path: fe.path.clone(),
+            is_dir: md.is_dir,
+        })
+    }
+
+    /// The write of this file, to be done when the transaction is committed,
+    /// and the path of the file.
+    pub(crate) fn pending_write(
+        &self,
+        target: Xell,
+        value: OwnValue,
+    ) -> Res<(PathBuf, transaction::PendingWrite)> {
+        let GroupType::Folder = self.ty else {
+            return inputres(
+                "cannot change the file size in a transaction, write the file instead",
+            );
+        };
+        let fe = self.fileentry()?;
+        let md = fe.metadata.as_ref().map_err(|e| e.clone())?;
+        if md.is_dir {
+            return fault("cannot write to a directory");
+        }
+        let write = transaction::PendingWrite {
+            target,
+            interpretation: "text".to_string(),
+            serial: value.to_string(),
+        };
+        Ok((fe.path.clone(), write))
+    }
+
     fn fileentry(&self) -> Res<&FileEntry> {
         self.files
             .list
@@ -291,6 +357,7 @@ impl Cell {
                 files: OwnRc::new(FileList {
                     list: indexmap,
                     full: true,
+                    deleted: Rc::default(),
                 }),
                 ty: GroupType::Folder,
             },
@@ -353,10 +420,14 @@ impl CellTrait for Cell {
                     return nores();
                 }
                 let files = read_files(&fe.path)?;
+                let deleted = r.deleted.borrow();
                 Ok(Group {
                     files: OwnRc::new(FileList {
                         list: files
                             .into_iter()
+                            .filter(|fileres| {
+                                !fileres.as_ref().is_ok_and(|fe| deleted.contains(&fe.path))
+                            })
                             .map(|fileres| {
                                 let fe = fileres?;
                                 let md = fe.metadata.as_ref().map_err(|e| e.clone())?;
@@ -364,6 +435,7 @@ impl CellTrait for Cell {
                             })
                             .collect::<Res<IndexMap<String, Res<FileEntry>>>>()?,
                         full: true,
+                        deleted: r.deleted.clone(),
                     }),
                     ty: GroupType::Folder,
                 })
@@ -431,6 +503,7 @@ impl CellTrait for Cell {
                         files: OwnRc::new(FileList {
                             list: indexmap,
                             full: true,
+                            deleted: Rc::default(),
                         }),
                         ty: GroupType::Folder,
                     },
@@ -525,6 +598,31 @@ impl GroupTrait for Group {
         };
         Ok(std::iter::once(cell))
     }
+
+    fn label_prefix_range(&self, prefix: &str) -> Res<Range<usize>> {
+        match self.ty {
+            GroupType::Folder => {
+                // files are sorted by name, see read_files; entries which
+                // cannot be read are sorted last and have no label to match
+                let files = self
+                    .files
+                    .read()
+                    .ok_or_else(|| lockerr("cannot read files"))?;
+                let readable = files
+                    .list
+                    .partition_point(|_, fe| fe.as_ref().is_ok_and(|fe| fe.metadata.is_ok()));
+                let sorted = files
+                    .list
+                    .get_range(..readable)
+                    .ok_or_else(|| faulterr("bad file list range"))?;
+                let start = sorted.partition_point(|name, _| name.as_str() < prefix);
+                let end = sorted
+                    .partition_point(|name, _| name.as_str() < prefix || name.starts_with(prefix));
+                Ok(start..end)
+            }
+            GroupType::FileAttributes(_) => nores(),
+        }
+    }
 }
 
 fn read_file(path: &Path) -> Res<FileEntry> {
@@ -628,3 +726,44 @@ fn read_files(parent_path: &Path) -> Res<Vec<Res<FileEntry>>> {
     });
     Ok(entries)
 }
+
+#[cfg(test)]
+#[test]
+fn test_label_prefix_range_with_errors() {
+    let entry = |name: &str| -> (String, Res<FileEntry>) {
+        let metadata = Metadata {
+            os_name: OsString::from(name),
+            name: name.to_string(),
+            filesize: 0,
+            is_dir: false,
+            is_link: false,
+        };
+        let fe = FileEntry {
+            path: PathBuf::from(name),
+            metadata: Ok(metadata),
+        };
+        (name.to_string(), Ok(fe))
+    };
+    let mut list: IndexMap<String, Res<FileEntry>> =
+        ["a.rs", "pa.rs", "path.rs", "zz.rs"].map(entry).into();
+    // unreadable entries are sorted last, whatever their key
+    list.insert("pa.err".to_string(), Err(noerrm("cannot read dir entry")));
+    let (name, mut fe) = entry("pa.md");
+    if let Ok(fe) = &mut fe {
+        fe.metadata = Err(noerrm("cannot query file metadata"));
+    }
+    list.insert(name, fe);
+
+    let group = Group {
+        files: OwnRc::new(FileList {
+            list,
+            full: true,
+            deleted: Rc::default(),
+        }),
+        ty: GroupType::Folder,
+    };
+    assert_eq!(group.label_prefix_range("pa").unwrap(), 1..3);
+    assert_eq!(group.label_prefix_range("pat").unwrap(), 2..3);
+    assert_eq!(group.label_prefix_range("q").unwrap(), 3..3);
+    assert_eq!(group.label_prefix_range("zz").unwrap(), 3..4);
+}
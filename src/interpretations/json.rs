@@ -55,6 +55,7 @@ pub(crate) struct CellReader {
 pub(crate) struct CellWriter {
     nodes: WriteNodeGroup,
     pos: usize,
+    is_root: bool,
 }
 
 #[derive(Debug)]
@@ -115,6 +116,24 @@ impl Cell {
     }
 }
 
+impl Cell {
+    /// Returns a deep copy of the node of this cell, with its label
+    fn labelled_node(&self) -> Res<(Option<String>, Node)> {
+        match self.group.nodes {
+            NodeGroup::Array(ref a) => {
+                let a = a.read().ok_or_else(|| lockerr("cannot read cell"))?;
+                let node = a.get(self.pos).ok_or_else(|| faulterr("bad pos"))?;
+                Ok((None, serde_to_node(node_to_serde(node)?)))
+            }
+            NodeGroup::Object(ref o) => {
+                let o = o.read().ok_or_else(|| lockerr("cannot read cell"))?;
+                let (label, node) = o.get_index(self.pos).ok_or_else(|| faulterr("bad pos"))?;
+                Ok((Some(label.clone()), serde_to_node(node_to_serde(node)?)))
+            }
+        }
+    }
+}
+
 impl CellTrait for Cell {
     type Group = Group;
     type CellReader = CellReader;
@@ -150,6 +169,7 @@ impl CellTrait for Cell {
                 }
             },
             pos: self.pos,
+            is_root: self.group.head.is_none(),
         })
     }
 
@@ -284,6 +304,21 @@ impl CellReaderTrait for CellReader {
 }
 
 impl CellWriterTrait for CellWriter {
+    fn set_ty(&mut self, new_type: &str) -> Res<()> {
+        let new_node = match new_type {
+            "object" => Node::Object(OwnRc::new(IndexMap::new())),
+            "array" => Node::Array(OwnRc::new(vec![])),
+            _ => return inputres(format!("cannot set json type to `{}`", new_type)),
+        };
+        let node = match self.nodes {
+            WriteNodeGroup::Array(ref mut a) => a.get_mut(self.pos),
+            WriteNodeGroup::Object(ref mut o) => o.get_index_mut(self.pos).map(|(_, n)| n),
+        }
+        .ok_or_else(|| faulterr("bad pos"))?;
+        *node = new_node;
+        Ok(())
+    }
+
     fn set_label(&mut self, label: OwnValue) -> Res<()> {
         match self.nodes {
             WriteNodeGroup::Array(_) => {
@@ -314,31 +349,24 @@ impl CellWriterTrait for CellWriter {
         Ok(())
     }
 
-    // fn delete(&mut self) -> Res<()> {
-    //     match self.group.nodes {
-    //         NodeGroup::Array(ref mut a) => {
-    //             let mut urca = a.urc();
-    //             let v = guard_some!(urca.get_mut(), {
-    //                 return Err(HErr::ExclusivityRequired {
-    //                     path: "".into(),
-    //                     op: "delete",
-    //                 });
-    //             });
-    //             v.remove(self.pos);
-    //         }
-    //         NodeGroup::Object(ref mut o) => {
-    //             let mut urco = o.urc();
-    //             let v = guard_some!(urco.get_mut(), {
-    //                 return Err(HErr::ExclusivityRequired {
-    //                     path: "".into(),
-    //                     op: "delete",
-    //                 });
-    //             });
-    //             v.remove(self.pos);
-    //         }
-    //     };
-    //     Ok(())
-    // }
+    fn detach(&mut self) -> Res<()> {
+        if self.is_root {
+            return inputres("cannot delete the root cell");
+        }
+        match self.nodes {
+            WriteNodeGroup::Array(ref mut a) => {
+                if self.pos >= a.len() {
+                    return fault("bad pos");
+                }
+                a.remove(self.pos);
+            }
+            WriteNodeGroup::Object(ref mut o) => {
+                o.shift_remove_index(self.pos)
+                    .ok_or_else(|| faulterr("bad pos"))?;
+            }
+        };
+        Ok(())
+    }
 }
 
 fn get_ty(node: &Node) -> &'static str {
@@ -420,6 +448,58 @@ impl GroupTrait for Group {
         };
         Ok(std::iter::once(cell))
     }
+
+    // a cell without a value is created as an empty object
+    fn create(&self, label: Option<OwnValue>, value: Option<OwnValue>) -> Res<Cell> {
+        let node = match value {
+            Some(value) => Node::Scalar(ownvalue_to_serde(value)),
+            None => Node::Object(OwnRc::new(IndexMap::new())),
+        };
+        let nodes = match label {
+            Some(label) => {
+                NodeGroup::Object(OwnRc::new(IndexMap::from([(label.to_string(), node)])))
+            }
+            None => NodeGroup::Array(OwnRc::new(vec![node])),
+        };
+        Ok(Cell {
+            group: Group {
+                nodes,
+                head: None,
+                indent: Rc::clone(&self.indent),
+            },
+            pos: 0,
+        })
+    }
+
+    // the cell is copied, so it can be added to more than one group
+    fn add(&self, index: Option<usize>, cell: Cell) -> Res<()> {
+        let (label, node) = cell.labelled_node()?;
+        match &self.nodes {
+            NodeGroup::Array(a) => {
+                let mut a = a.write().ok_or_else(|| lockerr("cannot write group"))?;
+                let index = index.unwrap_or(a.len());
+                if index > a.len() {
+                    return inputres(format!("cannot add cell at index {}", index));
+                }
+                a.insert(index, node);
+            }
+            NodeGroup::Object(o) => {
+                let label = guard_some!(label, {
+                    return inputres("cannot add a cell without a label to an object");
+                });
+                let mut o = o.write().ok_or_else(|| lockerr("cannot write group"))?;
+                if o.contains_key(&label) {
+                    return inputres(format!("cannot add cell, label `{}` already exists", label));
+                }
+                let index = index.unwrap_or(o.len());
+                if index > o.len() {
+                    return inputres(format!("cannot add cell at index {}", index));
+                }
+                o.shift_insert(index, label, node);
+            }
+        }
+        Ok(())
+    }
 }
 
 fn serde_to_value(sv: &SValue) -> Value<'_> {
@@ -439,7 +519,7 @@ fn serde_to_value(sv: &SValue) -> Value<'_> {
     }
 }
 
-fn ownvalue_to_serde(v: OwnValue) -> SValue {
+pub(crate) fn ownvalue_to_serde(v: OwnValue) -> SValue {
     match v {
         OwnValue::None => SValue::Null,
         OwnValue::Bool(b) => SValue::Bool(b),
@@ -170,6 +170,19 @@ impl CellWriterTrait for CellWriter {
             }
         }
     }
+
+    fn detach(&mut self) -> Res<()> {
+        match self.kind {
+            Kind::Root => inputres("cannot delete the text document"),
+            Kind::Line(i) => {
+                if i >= self.data.lines.len() {
+                    return nores();
+                }
+                self.data.lines.remove(i);
+                Ok(())
+            }
+        }
+    }
 }
 
 impl CellTrait for Cell {
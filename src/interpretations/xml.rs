@@ -14,7 +14,7 @@ use crate::{
     utils::{
         indentation::{IndentationReader, detect_indentation},
         ownrc::{OwnRc, ReadRc, WriteRc},
-        ownrcutils::read,
+        ownrcutils::{read, write},
     },
 };
 
@@ -489,6 +489,10 @@ impl CellReaderTrait for CellReader {
 }
 
 impl CellWriterTrait for CellWriter {
+    fn set_ty(&mut self, new_type: &str) -> Res<()> {
+        inputres(format!("cannot set xml type to `{}`", new_type))
+    }
+
     fn set_value(&mut self, value: OwnValue) -> Res<()> {
         match self {
             CellWriter::Node { nodes, pos } => match &mut nodes[*pos] {
@@ -512,6 +516,26 @@ impl CellWriterTrait for CellWriter {
         }
         Ok(())
     }
+
+    fn detach(&mut self) -> Res<()> {
+        match self {
+            CellWriter::Node { nodes, pos } => {
+                match nodes.get(*pos) {
+                    Some(Node::Document(_)) => return inputres("cannot delete document"),
+                    Some(_) => {}
+                    None => return fault("bad pos"),
+                }
+                nodes.remove(*pos);
+            }
+            CellWriter::Attr { nodes, pos } => {
+                if *pos >= nodes.len() {
+                    return fault("bad pos");
+                }
+                nodes.remove(*pos);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl CellTrait for Cell {
@@ -699,6 +723,109 @@ impl GroupTrait for Group {
             key: key.to_owned_value(),
         })
     }
+
+    // a labelled cell is created as an element (or attribute), an unlabelled
+    // cell as a text node
+    fn create(&self, label: Option<OwnValue>, value: Option<OwnValue>) -> Res<Cell> {
+        let value = value
+            .map(|v| v.as_cow_str().into_owned())
+            .unwrap_or_default();
+        let nodes = match (&self.nodes, label) {
+            (NodeGroup::Node(_), Some(label)) => {
+                NodeGroup::Node(OwnRc::new(vec![Node::Element((
+                    label.to_string(),
+                    OwnRc::new(vec![]),
+                    value,
+                    OwnRc::new(vec![]),
+                ))]))
+            }
+            (NodeGroup::Node(_), None) => NodeGroup::Node(OwnRc::new(vec![Node::Text(value)])),
+            (NodeGroup::Attr(_), Some(label)) => {
+                NodeGroup::Attr(OwnRc::new(vec![Attribute::Attribute(
+                    label.to_string(),
+                    value,
+                )]))
+            }
+            (NodeGroup::Attr(_), None) => {
+                return inputres("cannot create attribute without a label");
+            }
+        };
+        Ok(Cell {
+            group: Group {
+                nodes,
+                head: None,
+                indent: Rc::clone(&self.indent),
+            },
+            pos: 0,
+        })
+    }
+
+    // the cell is copied, so it can be added to more than one group
+    fn add(&self, index: Option<usize>, cell: Cell) -> Res<()> {
+        match (&self.nodes, &cell.group.nodes) {
+            (NodeGroup::Node(nodes), NodeGroup::Node(source)) => {
+                let node = copy_node(read(source)?.get(cell.pos).ok_or_else(noerr)?)?;
+                if matches!(node, Node::Document(_)) {
+                    return inputres("cannot add a document node");
+                }
+                let mut nodes = write(nodes)?;
+                let index = index.unwrap_or(nodes.len());
+                if index > nodes.len() {
+                    return inputres(format!("cannot add cell at index {}", index));
+                }
+                nodes.insert(index, node);
+            }
+            (NodeGroup::Attr(attrs), NodeGroup::Attr(source)) => {
+                let attr = copy_attr(read(source)?.get(cell.pos).ok_or_else(noerr)?);
+                let mut attrs = write(attrs)?;
+                let index = index.unwrap_or(attrs.len());
+                if index > attrs.len() {
+                    return inputres(format!("cannot add cell at index {}", index));
+                }
+                attrs.insert(index, attr);
+            }
+            (NodeGroup::Node(_), _) => return inputres("cannot add an attribute as a node"),
+            (NodeGroup::Attr(_), _) => return inputres("cannot add a node as an attribute"),
+        }
+        Ok(())
+    }
+}
+
+fn copy_node(node: &Node) -> Res<Node> {
+    fn copy_nodes(nodes: &OwnRc<Vec<Node>>) -> Res<OwnRc<Vec<Node>>> {
+        let copy = read(nodes)?
+            .iter()
+            .map(copy_node)
+            .collect::<Res<Vec<_>>>()?;
+        Ok(OwnRc::new(copy))
+    }
+    fn copy_attrs(attrs: &OwnRc<Vec<Attribute>>) -> Res<OwnRc<Vec<Attribute>>> {
+        Ok(OwnRc::new(read(attrs)?.iter().map(copy_attr).collect()))
+    }
+
+    Ok(match node {
+        Node::Document(nodes) => Node::Document(copy_nodes(nodes)?),
+        Node::Decl(attrs) => Node::Decl(copy_attrs(attrs)?),
+        Node::DocType(s) => Node::DocType(s.clone()),
+        Node::PI(s) => Node::PI(s.clone()),
+        Node::Element((name, attrs, text, nodes)) => Node::Element((
+            name.clone(),
+            copy_attrs(attrs)?,
+            text.clone(),
+            copy_nodes(nodes)?,
+        )),
+        Node::Text(s) => Node::Text(s.clone()),
+        Node::Comment(s) => Node::Comment(s.clone()),
+        Node::CData(data) => Node::CData(data.clone()),
+        Node::Error(s) => Node::Error(s.clone()),
+    })
+}
+
+fn copy_attr(attr: &Attribute) -> Attribute {
+    match attr {
+        Attribute::Attribute(k, v) => Attribute::Attribute(k.clone(), v.clone()),
+        Attribute::Error(e) => Attribute::Error(e.clone()),
+    }
 }
 
 impl From<XmlError> for HErr {
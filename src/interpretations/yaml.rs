@@ -34,6 +34,7 @@ pub(crate) struct CellReader {
 pub(crate) struct CellWriter {
     nodes: WriteNodeGroup,
     pos: usize,
+    is_root: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -128,6 +129,24 @@ impl Cell {
     }
 }
 
+impl Cell {
+    /// Returns a deep copy of the node of this cell, with its label
+    fn labelled_node(&self) -> Res<(Option<Yaml>, Node)> {
+        match self.group.nodes {
+            NodeGroup::Array(ref a) => {
+                let a = a.read().ok_or_else(|| lockerr("cannot read group"))?;
+                let node = a.get(self.pos).ok_or_else(|| faulterr("bad pos"))?;
+                Ok((None, node_from_yaml(&node_to_yaml(node)?)?))
+            }
+            NodeGroup::Object(ref o) => {
+                let o = o.read().ok_or_else(|| lockerr("cannot read group"))?;
+                let (label, node) = o.get_index(self.pos).ok_or_else(|| faulterr("bad pos"))?;
+                Ok((Some(label.clone()), node_from_yaml(&node_to_yaml(node)?)?))
+            }
+        }
+    }
+}
+
 impl CellReaderTrait for CellReader {
     fn ty(&self) -> Res<&str> {
         match self.nodes {
@@ -194,6 +213,21 @@ impl CellReaderTrait for CellReader {
 }
 
 impl CellWriterTrait for CellWriter {
+    fn set_ty(&mut self, new_type: &str) -> Res<()> {
+        let new_node = match new_type {
+            "object" => Node::Object(OwnRc::new(IndexMap::new())),
+            "array" => Node::Array(OwnRc::new(vec![])),
+            _ => return inputres(format!("cannot set yaml type to `{}`", new_type)),
+        };
+        let node = match self.nodes {
+            WriteNodeGroup::Array(ref mut a) => a.get_mut(self.pos),
+            WriteNodeGroup::Object(ref mut o) => o.get_index_mut(self.pos).map(|(_, n)| n),
+        }
+        .ok_or_else(|| faulterr("bad pos"))?;
+        *node = new_node;
+        Ok(())
+    }
+
     fn set_value(&mut self, value: OwnValue) -> Res<()> {
         match self.nodes {
             WriteNodeGroup::Array(ref mut a) => match a.get_mut(self.pos) {
@@ -207,6 +241,25 @@ impl CellWriterTrait for CellWriter {
         };
         Ok(())
     }
+
+    fn detach(&mut self) -> Res<()> {
+        if self.is_root {
+            return inputres("cannot delete the root cell");
+        }
+        match self.nodes {
+            WriteNodeGroup::Array(ref mut a) => {
+                if self.pos >= a.len() {
+                    return fault("bad pos");
+                }
+                a.remove(self.pos);
+            }
+            WriteNodeGroup::Object(ref mut o) => {
+                o.shift_remove_index(self.pos)
+                    .ok_or_else(|| faulterr("bad pos"))?;
+            }
+        };
+        Ok(())
+    }
 }
 
 impl CellTrait for Cell {
@@ -243,6 +296,7 @@ impl CellTrait for Cell {
                 }
             },
             pos: self.pos,
+            is_root: self.group.head.is_none(),
         })
     }
 
@@ -393,6 +447,55 @@ impl GroupTrait for Group {
         };
         Ok(std::iter::once(cell))
     }
+
+    // a cell without a value is created as an empty object
+    fn create(&self, label: Option<OwnValue>, value: Option<OwnValue>) -> Res<Cell> {
+        let node = match value {
+            Some(value) => Node::Scalar(ownvalue_to_yaml(value)?),
+            None => Node::Object(OwnRc::new(IndexMap::new())),
+        };
+        let nodes = match label {
+            Some(label) => NodeGroup::Object(OwnRc::new(IndexMap::from([(
+                Yaml::String(label.to_string()),
+                node,
+            )]))),
+            None => NodeGroup::Array(OwnRc::new(vec![node])),
+        };
+        Ok(Cell {
+            group: Group { nodes, head: None },
+            pos: 0,
+        })
+    }
+
+    // the cell is copied, so it can be added to more than one group
+    fn add(&self, index: Option<usize>, cell: Cell) -> Res<()> {
+        let (label, node) = cell.labelled_node()?;
+        match &self.nodes {
+            NodeGroup::Array(a) => {
+                let mut a = a.write().ok_or_else(|| lockerr("cannot write group"))?;
+                let index = index.unwrap_or(a.len());
+                if index > a.len() {
+                    return inputres(format!("cannot add cell at index {}", index));
+                }
+                a.insert(index, node);
+            }
+            NodeGroup::Object(o) => {
+                let Some(label) = label else {
+                    return inputres("cannot add a cell without a label to an object");
+                };
+                let mut o = o.write().ok_or_else(|| lockerr("cannot write group"))?;
+                if o.contains_key(&label) {
+                    return inputres("cannot add cell, label already exists");
+                }
+                let index = index.unwrap_or(o.len());
+                if index > o.len() {
+                    return inputres(format!("cannot add cell at index {}", index));
+                }
+                o.shift_insert(index, label, node);
+            }
+        }
+        Ok(())
+    }
 }
 
 fn node_from_yaml(y: &Yaml) -> Res<Node> {
@@ -1,7 +1,9 @@
+use std::process::ExitCode;
+
 use hiallib::{
     api::*,
     config::{self, ColorPalette},
-    prog::{ExecutionContext, Program, ProgramParams},
+    prog::{ExecutionContext, OutputFormat, Program, ProgramParams},
     *,
 };
 
@@ -10,22 +12,46 @@ struct Args {
     depth: Option<usize>,
     breadth: Option<usize>,
     color_palette: ColorPalette,
+    output: OutputFormat,
+    match_limit: Option<usize>,
+    count: bool,
+    dry_run: bool,
+    program_file: Option<String>,
     program: String,
 }
 
-fn main() -> Res<()> {
+// exit statuses, like grep
+const EXIT_MATCH: u8 = 0;
+const EXIT_NO_MATCH: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::from(EXIT_MATCH),
+        Ok(false) => ExitCode::from(EXIT_NO_MATCH),
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Runs the program given in the arguments. Returns false if the program
+/// has path or tree statements and none of them matched anything.
+fn run() -> Res<bool> {
     let args = parse_args(&config::load_main_config()?)?;
 
     if args.program.is_empty() {
-        eprintln!("No program given.");
-        return Ok(());
+        return inputres("no program given");
     }
 
     debug!("Command: run {}", args.program);
     let mut exec_ctx = ExecutionContext::default();
 
-    if let Some(prelude_text) = config::load_prelude_text()? {
-        let prelude = Program::parse(&prelude_text)
+    // the prelude functions borrow from the prelude text
+    let prelude_text = config::load_prelude_text()?;
+    if let Some(prelude_text) = &prelude_text {
+        let prelude = Program::parse(prelude_text)
             .map_err(|e| caused(HErrKind::Input, "prelude error", e))?;
         prelude.run_in_context(ProgramParams::default(), &mut exec_ctx)?;
     }
@@ -35,9 +61,14 @@ fn main() -> Res<()> {
         print_depth: args.depth.unwrap_or(usize::MAX),
         print_breadth: args.breadth.unwrap_or(0),
         color_palette: args.color_palette,
+        output: args.output,
+        match_limit: args.match_limit,
+        count: args.count,
+        dry_run: args.dry_run,
     };
+    let (queries, matches) = (exec_ctx.queries(), exec_ctx.matches());
     program.run_in_context(params, &mut exec_ctx)?;
-    Ok(())
+    Ok(exec_ctx.queries() == queries || exec_ctx.matches() > matches)
 }
 
 fn parse_args(config: &config::MainConfig) -> Res<Args> {
@@ -59,6 +90,32 @@ fn parse_args(config: &config::MainConfig) -> Res<Args> {
             "-b" if in_flags => {
                 args.breadth = args_iter.next().and_then(|s| s.parse().ok());
             }
+            "--output" if in_flags => {
+                let format = args_iter.next().unwrap_or_default();
+                args.output = format.parse()?;
+            }
+            "--limit" if in_flags => {
+                let limit = args_iter.next().unwrap_or_default();
+                args.match_limit =
+                    Some(limit.parse().map_err(|_| {
+                        inputerr(format!("--limit expects a number, got `{}`", limit))
+                    })?);
+            }
+            "--first" if in_flags => {
+                args.match_limit = Some(1);
+            }
+            "--count" if in_flags => {
+                args.count = true;
+            }
+            "-f" if in_flags => {
+                args.program_file = args_iter.next();
+                if args.program_file.is_none() {
+                    return inputres("-f expects a program file");
+                }
+            }
+            "--dry-run" if in_flags => {
+                args.dry_run = true;
+            }
             "--no-color" if in_flags => {
                 args.color_palette = ColorPalette::None;
             }
@@ -82,5 +139,19 @@ fn parse_args(config: &config::MainConfig) -> Res<Args> {
         }
     }
 
+    if let Some(file) = &args.program_file {
+        if !args.program.is_empty() {
+            return inputres("cannot run both a program file and a program argument");
+        }
+        let text = std::fs::read_to_string(file).map_err(|e| {
+            caused(
+                HErrKind::IO,
+                format!("cannot read program file: {}", file),
+                e,
+            )
+        })?;
+        args.program = Program::strip_comments(&text);
+    }
+
     Ok(args)
 }
@@ -0,0 +1,153 @@
+use crate::api::*;
+use crate::config::ColorPalette;
+use crate::pprint::render::{DiffSign, render_diff_line};
+
+/// Prints a diff tree, as returned by `Xell::diff`, as `-`/`+` lines.
+/// Removed cells are printed with `-`, added cells with `+`, changed values
+/// with both, and the changed parents of these cells as context lines.
+/// Unchanged cells are not printed.
+pub fn pprint_diff(cell: &Xell, color_palette: ColorPalette) {
+    match diff_lines(cell, color_palette) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(e) => eprintln!("pprint error: {:?}", e),
+    }
+}
+
+pub(crate) fn diff_lines(cell: &Xell, color_palette: ColorPalette) -> Res<Vec<String>> {
+    let mut lines = vec![];
+    if cell.read().ty()? == "diff_root" {
+        for child in cell.sub() {
+            diff_node_lines(&child.err()?, "", 0, color_palette, &mut lines)?;
+        }
+    } else {
+        diff_node_lines(cell, "", 0, color_palette, &mut lines)?;
+    }
+    Ok(lines)
+}
+
+fn diff_node_lines(
+    cell: &Xell,
+    prefix: &str,
+    depth: usize,
+    color_palette: ColorPalette,
+    lines: &mut Vec<String>,
+) -> Res<()> {
+    let reader = cell.read().err()?;
+    let mut markers = vec![];
+    let mut old_value = None;
+    for attr in cell.attr() {
+        let attr = attr.err()?;
+        let attr_reader = attr.read().err()?;
+        match attr_reader.label() {
+            Ok(Value::Str("diff_old_value")) => {
+                old_value = Some(attr_reader.value()?.to_owned_value())
+            }
+            Ok(Value::Str(label @ ("diff_old" | "diff_new" | "diff_changed"))) => {
+                markers.push(label.to_string())
+            }
+            _ => {}
+        }
+    }
+    let has_marker = |marker: &str| markers.iter().any(|m| m == marker);
+    let value = reader_value(&reader)?;
+    let text = |value: Option<&OwnValue>| line_text(&reader, prefix, depth, value);
+
+    if has_marker("diff_old") || has_marker("diff_new") {
+        let sign = if has_marker("diff_old") {
+            DiffSign::Old
+        } else {
+            DiffSign::New
+        };
+        lines.push(render_diff_line(
+            sign,
+            &text(value.as_ref())?,
+            color_palette,
+        ));
+        for (child_prefix, child) in children(cell) {
+            subtree_lines(&child?, child_prefix, depth + 1, sign, color_palette, lines)?;
+        }
+        return Ok(());
+    }
+    if let Some(old_value) = old_value {
+        lines.push(render_diff_line(
+            DiffSign::Old,
+            &text(Some(&old_value))?,
+            color_palette,
+        ));
+        lines.push(render_diff_line(
+            DiffSign::New,
+            &text(value.as_ref())?,
+            color_palette,
+        ));
+    } else if has_marker("diff_changed") {
+        lines.push(render_diff_line(
+            DiffSign::Context,
+            &text(value.as_ref())?,
+            color_palette,
+        ));
+    }
+    if has_marker("diff_changed") {
+        for (child_prefix, child) in children(cell) {
+            diff_node_lines(&child?, child_prefix, depth + 1, color_palette, lines)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints a whole removed or added subtree
+fn subtree_lines(
+    cell: &Xell,
+    prefix: &str,
+    depth: usize,
+    sign: DiffSign,
+    color_palette: ColorPalette,
+    lines: &mut Vec<String>,
+) -> Res<()> {
+    let reader = cell.read().err()?;
+    let text = line_text(&reader, prefix, depth, reader_value(&reader)?.as_ref())?;
+    lines.push(render_diff_line(sign, &text, color_palette));
+    for (child_prefix, child) in children(cell) {
+        subtree_lines(&child?, child_prefix, depth + 1, sign, color_palette, lines)?;
+    }
+    Ok(())
+}
+
+fn reader_value(reader: &CellReader) -> Res<Option<OwnValue>> {
+    match reader.value() {
+        Ok(value) => Ok(Some(value.to_owned_value())),
+        Err(e) if e.kind == HErrKind::None => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn line_text(
+    reader: &CellReader,
+    prefix: &str,
+    depth: usize,
+    value: Option<&OwnValue>,
+) -> Res<String> {
+    let indent = "  ".repeat(depth);
+    Ok(match (reader.label(), value) {
+        (Ok(label), Some(value)) => format!("{}{}{}: {}", indent, prefix, label, value),
+        (Ok(label), None) => format!("{}{}{}:", indent, prefix, label),
+        (Err(_), Some(value)) => format!("{}{}{}", indent, prefix, value),
+        (Err(_), None) => format!("{}{}{}", indent, prefix, reader.ty()?),
+    })
+}
+
+/// The attributes (except the diff markers) and the sub cells of a diff node
+fn children(cell: &Xell) -> impl Iterator<Item = (&'static str, Res<Xell>)> {
+    let attrs = cell.attr().into_iter().filter(|attr| {
+        !matches!(
+            attr.read().label(),
+            Ok(Value::Str(label)) if label.starts_with("diff_")
+        )
+    });
+    attrs
+        .map(|attr| ("@", attr.err()))
+        .chain(cell.sub().into_iter().map(|sub| ("", sub.err())))
+}
@@ -240,4 +240,9 @@ fn format_bytes(bytes: &[u8]) -> String {
     value
 }
 
+mod diff;
 mod render;
+
+#[cfg(test)]
+pub(crate) use diff::diff_lines;
+pub use diff::pprint_diff;
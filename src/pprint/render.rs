@@ -12,6 +12,8 @@ const COLOR_TREE_DARK: &str = "2";
 const COLOR_EDGE_PREFIX_DARK: &str = "34";
 const COLOR_KEY_DARK: &str = "39";
 const COLOR_ERROR_DARK: &str = "31";
+const COLOR_DIFF_OLD_DARK: &str = "31";
+const COLOR_DIFF_NEW_DARK: &str = "32";
 
 const COLOR_INTERPRETATION_LIGHT: &str = "2";
 const COLOR_TYPE_STRING_LIGHT: &str = "38;5;22";
@@ -21,6 +23,8 @@ const COLOR_TREE_LIGHT: &str = "2";
 const COLOR_EDGE_PREFIX_LIGHT: &str = "24";
 const COLOR_KEY_LIGHT: &str = "39";
 const COLOR_ERROR_LIGHT: &str = "160";
+const COLOR_DIFF_OLD_LIGHT: &str = "160";
+const COLOR_DIFF_NEW_LIGHT: &str = "28";
 
 pub(crate) struct TreePrefix {
     pub(crate) ancestors_have_next: Vec<bool>,
@@ -40,6 +44,17 @@ pub(crate) struct LineData {
     pub(crate) empty: bool,
 }
 
+/// The kind of a line in a printed diff
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DiffSign {
+    // removed, printed with `-`
+    Old,
+    // added, printed with `+`
+    New,
+    // a changed parent of other diff lines
+    Context,
+}
+
 pub(crate) enum LineValue {
     Inline(String),
     Bytes(String),
@@ -149,6 +164,15 @@ pub(crate) fn render_line(
     Ok(line)
 }
 
+pub(crate) fn render_diff_line(sign: DiffSign, text: &str, color_palette: ColorPalette) -> String {
+    let palette = palette_colors(color_palette);
+    match sign {
+        DiffSign::Old => colorize(palette.diff_old, &format!("- {}", text)),
+        DiffSign::New => colorize(palette.diff_new, &format!("+ {}", text)),
+        DiffSign::Context => format!("  {}", colorize(palette.key, text)),
+    }
+}
+
 impl PPrintTheme {
     fn unicode() -> Self {
         Self {
@@ -219,6 +243,8 @@ struct PaletteColors {
     edge_prefix: &'static str,
     key: &'static str,
     error: &'static str,
+    diff_old: &'static str,
+    diff_new: &'static str,
     hash_palette: &'static [u8],
 }
 
@@ -234,6 +260,8 @@ fn palette_colors(color_palette: ColorPalette) -> PaletteColors {
             edge_prefix: COLOR_EDGE_PREFIX_DARK,
             key: COLOR_KEY_DARK,
             error: COLOR_ERROR_DARK,
+            diff_old: COLOR_DIFF_OLD_DARK,
+            diff_new: COLOR_DIFF_NEW_DARK,
             hash_palette: &[75, 79, 86, 110, 117, 141, 149, 159, 177, 186, 207, 216],
         },
         ColorPalette::Light => PaletteColors {
@@ -245,6 +273,8 @@ fn palette_colors(color_palette: ColorPalette) -> PaletteColors {
             edge_prefix: COLOR_EDGE_PREFIX_LIGHT,
             key: COLOR_KEY_LIGHT,
             error: COLOR_ERROR_LIGHT,
+            diff_old: COLOR_DIFF_OLD_LIGHT,
+            diff_new: COLOR_DIFF_NEW_LIGHT,
             hash_palette: &[18, 23, 24, 25, 31, 52, 53, 58, 88, 94, 124, 130],
         },
     }
@@ -0,0 +1,102 @@
+use crate::api::*;
+
+/// Copies a source cell into a destination cell. A leaf source writes its
+/// value into the destination. A source with sub cells is merged into the
+/// sub group of the destination: children are matched by label (or by index
+/// in groups without unique labels), existing children are copied into
+/// recursively and missing children are created and added to the group.
+/// Objects and arrays are not copied into each other.
+pub(crate) fn copy_cell(source: &Xell, destination: &Xell) -> Res<()> {
+    let group = match source.sub().err() {
+        Ok(group) if !group.is_empty() => group,
+        Ok(_) => return copy_value(source, destination),
+        Err(e) if e.kind == HErrKind::None => return copy_value(source, destination),
+        Err(e) => return Err(e),
+    };
+    let target = match destination.sub().err() {
+        Ok(target) => target,
+        Err(e) if e.kind == HErrKind::None => {
+            return inputres(format!(
+                "cannot copy a subtree into `{}`, which has no sub group",
+                destination.path().unwrap_or_default()
+            ));
+        }
+        Err(e) => return Err(e),
+    };
+    let source_kind = container_kind(source.read().err()?.ty()?);
+    let destination_kind = container_kind(destination.read().err()?.ty()?);
+    if let (Some(source_kind), Some(destination_kind)) = (source_kind, destination_kind)
+        && source_kind != destination_kind
+    {
+        return inputres(format!(
+            "cannot copy `{}`, an {}, into `{}`, an {}",
+            source.path().unwrap_or_default(),
+            source_kind,
+            destination.path().unwrap_or_default(),
+            destination_kind
+        ));
+    }
+    let by_label = target.label_type().unique_labels;
+    for child in group {
+        let child = child.err()?;
+        let reader = child.read().err()?;
+        let label = match reader.label() {
+            Ok(label) => Some(label.to_owned_value()),
+            Err(e) if e.kind == HErrKind::None => None,
+            Err(e) => return Err(e),
+        };
+        let existing = match (&label, by_label) {
+            (Some(label), true) => target.get(label.as_value()),
+            _ => target.at(reader.index()?),
+        };
+        match existing.err() {
+            Ok(existing) => copy_cell(&child, &existing)?,
+            Err(e) if e.kind == HErrKind::None => {
+                let is_leaf = child.sub().err().is_err_and(|e| e.kind == HErrKind::None);
+                let value = if is_leaf {
+                    Some(reader.value()?.to_owned_value())
+                } else {
+                    None
+                };
+                let new_cell = match target.create(label.clone(), value) {
+                    Err(e) if e.kind == HErrKind::None => {
+                        return inputres(format!(
+                            "cannot create cells in `{}`",
+                            destination.path().unwrap_or_default()
+                        ));
+                    }
+                    new_cell => new_cell?,
+                };
+                if !is_leaf && reader.ty()? == "array" {
+                    // created cells without a value are objects by default
+                    new_cell.write().ty("array")?;
+                }
+                target.add(None, new_cell)?;
+                if !is_leaf {
+                    let added = match &label {
+                        Some(label) if by_label => target.get(label.as_value()),
+                        _ => target.at(target.len()? - 1),
+                    };
+                    copy_cell(&child, &added.err()?)?;
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// the kind of a container in the tree interpretations, which name their
+// objects differently
+fn container_kind(ty: &str) -> Option<&'static str> {
+    match ty {
+        "array" => Some("array"),
+        "object" | "table" | "document" => Some("object"),
+        _ => None,
+    }
+}
+
+fn copy_value(source: &Xell, destination: &Xell) -> Res<()> {
+    let value = source.read().err()?.value()?.to_owned_value();
+    destination.write().value(value)
+}
@@ -0,0 +1,143 @@
+use crate::{
+    api::*,
+    prog::{
+        ExecutionContext, Path, RValue,
+        path::{Index, PathItem},
+        rvalue,
+        searcher::Searcher,
+    },
+};
+
+/// Assigns a value to the cells matched by a path, creating the missing cells
+/// on the way, like `mkdir -p`. Only plain labels (`/name`, `@name`) and
+/// indices (`/[2]`) are created: a label followed by an index is created as
+/// an array, any other intermediate cell as an object, and the last cell holds
+/// the value. The other path items are searched as usual.
+pub(crate) fn assign_creating<'a>(
+    ctx: &ExecutionContext<'a>,
+    start: Xell,
+    path: &Path<'a>,
+    value: &RValue,
+) -> Res<()> {
+    assign_items(ctx, start, &path.0, value)
+}
+
+#[derive(Clone, Copy)]
+enum Key<'a> {
+    Label(&'a str),
+    Index(usize),
+}
+
+fn assign_items<'a>(
+    ctx: &ExecutionContext<'a>,
+    cell: Xell,
+    items: &[PathItem<'a>],
+    value: &RValue,
+) -> Res<()> {
+    let Some((item, rest)) = items.split_first() else {
+        return rvalue::assign(&cell, value);
+    };
+    match creatable_key(item) {
+        Some((relation, key)) => {
+            let child = get_or_create(&cell, relation, key, rest, value)?;
+            assign_items(ctx, child, rest, value)
+        }
+        None => {
+            let matches = Searcher::new_in_context(cell, Path(vec![item.clone()]), ctx)
+                .collect::<Res<Vec<_>>>()?;
+            for m in matches {
+                assign_items(ctx, m, rest, value)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn creatable_key<'a>(item: &PathItem<'a>) -> Option<(Relation, Key<'a>)> {
+    let PathItem::Normal(item) = item else {
+        return None;
+    };
+    if !item.filters.is_empty() || !matches!(item.relation, Relation::Sub | Relation::Attr) {
+        return None;
+    }
+    match (&item.selector, item.index) {
+        (Some(Selector::Str(label)), None) => Some((item.relation, Key::Label(label))),
+        (None, Some(Index::At(i))) if i >= 0 => Some((item.relation, Key::Index(i as usize))),
+        _ => None,
+    }
+}
+
+fn get_or_create(
+    cell: &Xell,
+    relation: Relation,
+    key: Key,
+    rest: &[PathItem],
+    value: &RValue,
+) -> Res<Xell> {
+    let group = match relation {
+        Relation::Attr => cell.attr(),
+        _ => cell.sub(),
+    };
+    let group = match group.err() {
+        Ok(group) => group,
+        Err(e) if e.kind == HErrKind::None => {
+            return inputres(format!(
+                "cannot create cells in `{}`, which has no {} group",
+                cell.path().unwrap_or_default(),
+                if relation == Relation::Attr {
+                    "attr"
+                } else {
+                    "sub"
+                }
+            ));
+        }
+        Err(e) => return Err(e),
+    };
+    let existing = match key {
+        Key::Label(label) => group.get(label),
+        Key::Index(index) => group.at(index),
+    };
+    match existing.err() {
+        Ok(existing) => return Ok(existing),
+        Err(e) if e.kind == HErrKind::None => {}
+        Err(e) => return Err(e),
+    }
+
+    let label = match key {
+        Key::Label(label) => Some(OwnValue::from(label.to_string())),
+        Key::Index(index) => {
+            let len = group.len()?;
+            if index != len {
+                return inputres(format!(
+                    "cannot create cell [{}] in `{}`, only [{}] can be appended",
+                    index,
+                    cell.path().unwrap_or_default(),
+                    len
+                ));
+            }
+            None
+        }
+    };
+    let new_value = match value {
+        RValue::Value(value) if rest.is_empty() => Some(value.clone()),
+        _ => None,
+    };
+    let new_cell = match group.create(label, new_value) {
+        Err(e) if e.kind == HErrKind::None => {
+            return inputres(format!(
+                "cannot create cells in `{}`",
+                cell.path().unwrap_or_default()
+            ));
+        }
+        new_cell => new_cell?,
+    };
+    if rest
+        .first()
+        .and_then(creatable_key)
+        .is_some_and(|(_, key)| matches!(key, Key::Index(_)))
+    {
+        new_cell.write().ty("array")?;
+    }
+    group.add(None, new_cell)?;
+    group.at(group.len()? - 1).err()
+}
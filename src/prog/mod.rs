@@ -1,14 +1,21 @@
+pub(crate) mod copy;
+pub(crate) mod create;
+pub(crate) mod output;
 pub(crate) mod path;
 pub(crate) mod program;
+pub(crate) mod rvalue;
 pub(crate) mod searcher;
+pub(crate) mod tree;
 pub(crate) mod url;
 
 pub(super) mod parse_path;
 pub(super) mod parse_program;
 pub(super) mod parse_url;
 
+pub use output::OutputFormat;
 pub use path::{Path, PathStart};
 pub use program::{ExecutionContext, Program, ProgramParams};
+pub use rvalue::{BinaryOp, RValue};
 
 use chumsky::error::Rich;
 use chumsky::span::SimpleSpan;
@@ -0,0 +1,100 @@
+use std::{io::Write, str::FromStr};
+
+use serde_json::Value as SValue;
+
+use crate::{
+    api::*,
+    pprint::pprint,
+    prog::{ProgramParams, tree::cell_to_serde},
+};
+
+/// How the matches of path and tree statements are printed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// decorated tree, for humans
+    #[default]
+    Tree,
+    /// each match as a pretty printed json subtree
+    Json,
+    /// each match as a yaml document
+    Yaml,
+    /// each match as a json subtree on its own line
+    Ndjson,
+    /// the path of each match
+    Paths,
+    /// the value of each match, with strings unquoted (like `jq -r`)
+    Values,
+}
+
+impl FromStr for OutputFormat {
+    type Err = HErr;
+
+    fn from_str(s: &str) -> Res<Self> {
+        match s {
+            "tree" => Ok(OutputFormat::Tree),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "paths" => Ok(OutputFormat::Paths),
+            "values" => Ok(OutputFormat::Values),
+            _ => inputres(format!(
+                "unknown output format `{}`, expected one of: tree, json, yaml, ndjson, paths, values",
+                s
+            )),
+        }
+    }
+}
+
+pub(crate) fn print_match(cell: &Xell, params: &ProgramParams) -> Res<()> {
+    match params.output {
+        OutputFormat::Tree => pprint(
+            cell,
+            params.print_depth,
+            params.print_breadth,
+            params.color_palette,
+        ),
+        OutputFormat::Json => println!("{}", to_json(&cell_to_serde(cell)?, true)?),
+        OutputFormat::Ndjson => println!("{}", to_json(&cell_to_serde(cell)?, false)?),
+        OutputFormat::Yaml => {
+            let yaml = serde_yaml::to_string(&cell_to_serde(cell)?)
+                .map_err(|e| caused(HErrKind::Internal, "cannot serialize to yaml", e))?;
+            print!("---\n{}", yaml);
+        }
+        OutputFormat::Paths => println!("{}", cell.path()?),
+        OutputFormat::Values => print_value(cell)?,
+    }
+    Ok(())
+}
+
+fn to_json(value: &SValue, pretty: bool) -> Res<String> {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    json.map_err(|e| caused(HErrKind::Internal, "cannot serialize to json", e))
+}
+
+// leaves are printed as raw values, byte values (e.g. file contents) as they
+// are, and cells with sub cells as single line json
+fn print_value(cell: &Xell) -> Res<()> {
+    let json = cell_to_serde(cell)?;
+    if json.is_array() || json.is_object() {
+        println!("{}", to_json(&json, false)?);
+        return Ok(());
+    }
+    let reader = cell.read().err()?;
+    match reader.value() {
+        Ok(Value::None) => println!("null"),
+        Ok(Value::Bytes) => {
+            let mut stdout = std::io::stdout().lock();
+            std::io::copy(&mut reader.value_read()?, &mut stdout)
+                .and_then(|_| stdout.flush())
+                .map_err(|e| caused(HErrKind::IO, "cannot write value", e))?;
+        }
+        Ok(value) => println!("{}", value),
+        Err(e) if e.kind == HErrKind::None => println!("null"),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use super::{ParseError, convert_error};
 use crate::{
     api::*,
-    prog::{parse_url::*, path::*},
+    prog::{BinaryOp, RValue, parse_url::*, path::*},
 };
 use chumsky::prelude::*;
 use std::str::FromStr;
@@ -24,14 +24,32 @@ pub fn parse_path_with_starter(input: &str) -> Res<(PathStart<'_>, Path<'_>)> {
 
 pub(super) fn path_with_starter_parser<'a>()
 -> impl Parser<'a, &'a str, (PathStart<'a>, Path<'a>), extra::Err<ParseError<'a>>> + Clone {
-    path_start_parser()
-        .then_ignore(ws())
-        .then(path_items_parser())
-        .labelled("path")
+    recursive(|path_with_starter| {
+        path_start_with_args_parser(path_with_starter)
+            .then_ignore(ws())
+            .then(path_items_parser())
+    })
+    .labelled("path")
 }
 
 fn path_start_parser<'a>()
 -> impl Parser<'a, &'a str, PathStart<'a>, extra::Err<ParseError<'a>>> + Clone {
+    path_start_with_args_parser(path_with_starter_parser())
+}
+
+// the call arguments are paths with a start, parsed by `arg`
+fn path_start_with_args_parser<'a>(
+    arg: impl Parser<'a, &'a str, (PathStart<'a>, Path<'a>), extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, PathStart<'a>, extra::Err<ParseError<'a>>> + Clone {
+    let path_start_call = identifier_parser()
+        .then(
+            arg.padded_by(ws())
+                .separated_by(just(','))
+                .collect::<Vec<_>>()
+                .delimited_by(just('('), just(')')),
+        )
+        .map(|(name, args)| PathStart::Call(name, args))
+        .labelled("path_start_call");
     let path_start_url = url_parser().map(PathStart::Url).labelled("path_start_url");
     let path_start_var = just('$')
         .ignore_then(identifier_parser())
@@ -62,9 +80,16 @@ fn path_start_parser<'a>()
     let path_start_string = string_parser()
         .map(PathStart::String)
         .labelled("path_start_string");
+    // only before an elevation, `-` alone or followed by a label is a file
+    let path_start_stdin = just('-')
+        .then_ignore(just('^').rewind())
+        .to(PathStart::Stdin)
+        .labelled("path_start_stdin");
     choice((
         path_start_url,
         path_start_var,
+        path_start_call,
+        path_start_stdin,
         path_start_file,
         path_start_string,
     ))
@@ -74,55 +99,14 @@ fn path_start_parser<'a>()
 pub(super) fn path_items_parser<'a>()
 -> impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone {
     recursive(|path_items| {
-        let path_item_selector = path_code_points()
-            .map(Selector::from)
-            .labelled("path_item_selector");
-        let path_item_index = just('[')
-            .ignore_then(number_isize_parser())
-            .then_ignore(just(']'))
-            .labelled("path_item_index");
-        let type_expression = just(':')
-            .ignore_then(identifier_parser())
-            .map(|ty| Expression::Type { ty })
-            .labelled("type expression");
-        let ternary_expression = path_item_parser(path_items.clone())
-            .repeated()
-            .collect::<Vec<_>>()
-            .map(Path)
-            .then(
-                ws().ignore_then(operation_parser().then_ignore(ws()).then(rvalue_parser()))
-                    .or_not(),
-            )
-            .try_map(|(left, op_right), span| {
-                if left.0.is_empty() && op_right.is_none() {
-                    return Err(chumsky::error::Rich::custom(
-                        span,
-                        "empty ternary expression",
-                    ));
-                }
-                Ok(Expression::Ternary { left, op_right })
-            })
-            .labelled("ternary expression");
-        let expression = ws()
-            .ignore_then(
-                choice((type_expression, ternary_expression))
-                    .separated_by(just('|'))
-                    .at_least(1)
-                    .collect::<Vec<_>>(),
-            )
-            .map(|expressions: Vec<Expression<'_>>| {
-                if expressions.len() == 1 {
-                    expressions.into_iter().next().unwrap()
-                } else {
-                    Expression::Or { expressions }
-                }
-            })
-            .labelled("expression");
-        let filter = just('[')
-            .ignore_then(expression)
-            .then_ignore(just(']'))
-            .map(|expr| Filter { expr })
-            .labelled("filter");
+        let path_item_selector = path_item_selector_parser();
+        let path_item_index = path_item_index_parser();
+        let filter = filter_parser(
+            path_item_parser(path_items.clone())
+                .repeated()
+                .collect::<Vec<_>>()
+                .map(Path),
+        );
 
         let interpretation_param_longform = identifier_parser()
             .then_ignore(ws())
@@ -169,88 +153,51 @@ pub(super) fn path_items_parser<'a>()
             .then_ignore(ws())
             .then(path_item_index.or_not())
             .then_ignore(ws())
-            .then(filter.repeated().collect::<Vec<_>>())
+            .then(filters_and_capture_parser(filter.clone()))
             .then_ignore(ws())
-            .try_map(|(((relation, selector), index), filters), span| {
-                if selector.is_none() && index.is_none() {
-                    return Err(chumsky::error::Rich::custom(
-                        span,
-                        "normal path item requires selector or index",
-                    ));
-                }
-                if relation == Relation::Field && !filters.is_empty() {
-                    return Err(chumsky::error::Rich::custom(
-                        span,
-                        "field relation cannot have filters",
-                    ));
-                }
-                Ok(PathItem::Normal(NormalPathItem {
-                    relation,
-                    selector,
-                    index,
-                    filters,
-                }))
-            })
+            .try_map(
+                |(((relation, selector), index), (filters, capture)), span| {
+                    if selector.is_none() && index.is_none() {
+                        return Err(chumsky::error::Rich::custom(
+                            span,
+                            "normal path item requires selector or index",
+                        ));
+                    }
+                    if relation == Relation::Field && !filters.is_empty() {
+                        return Err(chumsky::error::Rich::custom(
+                            span,
+                            "field relation cannot have filters",
+                        ));
+                    }
+                    Ok(PathItem::Normal(NormalPathItem {
+                        relation,
+                        selector,
+                        index,
+                        filters,
+                        capture,
+                    }))
+                },
+            )
             .labelled("normal path item");
 
-        choice((elevation_path_item, normal_path_item))
-            .repeated()
-            .collect::<Vec<_>>()
-            .map(Path)
-            .labelled("path_items")
+        choice((
+            parent_path_item_parser(filter.clone()),
+            elevation_path_item,
+            normal_path_item,
+        ))
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(Path)
+        .labelled("path_items")
     })
 }
 
 fn path_item_parser<'a>(
     path_items: impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
 ) -> impl Parser<'a, &'a str, PathItem<'a>, extra::Err<ParseError<'a>>> + Clone {
-    let path_item_selector = path_code_points()
-        .map(Selector::from)
-        .labelled("path_item_selector");
-    let path_item_index = just('[')
-        .ignore_then(number_isize_parser())
-        .then_ignore(just(']'))
-        .labelled("path_item_index");
-    let type_expression = just(':')
-        .ignore_then(identifier_parser())
-        .map(|ty| Expression::Type { ty })
-        .labelled("type expression");
-    let ternary_expression = path_items
-        .clone()
-        .then(
-            ws().ignore_then(operation_parser().then_ignore(ws()).then(rvalue_parser()))
-                .or_not(),
-        )
-        .try_map(|(left, op_right), span| {
-            if left.0.is_empty() && op_right.is_none() {
-                return Err(chumsky::error::Rich::custom(
-                    span,
-                    "empty ternary expression",
-                ));
-            }
-            Ok(Expression::Ternary { left, op_right })
-        })
-        .labelled("ternary expression");
-    let expression = ws()
-        .ignore_then(
-            choice((type_expression, ternary_expression))
-                .separated_by(just('|'))
-                .at_least(1)
-                .collect::<Vec<_>>(),
-        )
-        .map(|expressions: Vec<Expression<'_>>| {
-            if expressions.len() == 1 {
-                expressions.into_iter().next().unwrap()
-            } else {
-                Expression::Or { expressions }
-            }
-        })
-        .labelled("expression");
-    let filter = just('[')
-        .ignore_then(expression)
-        .then_ignore(just(']'))
-        .map(|expr| Filter { expr })
-        .labelled("filter");
+    let path_item_selector = path_item_selector_parser();
+    let path_item_index = path_item_index_parser();
+    let filter = filter_parser(path_items.clone());
     let interpretation_param_longform = identifier_parser()
         .then_ignore(ws())
         .then_ignore(just('='))
@@ -295,42 +242,62 @@ fn path_item_parser<'a>(
         .then_ignore(ws())
         .then(path_item_index.or_not())
         .then_ignore(ws())
-        .then(filter.repeated().collect::<Vec<_>>())
+        .then(filters_and_capture_parser(filter.clone()))
         .then_ignore(ws())
-        .try_map(|(((relation, selector), index), filters), span| {
-            if selector.is_none() && index.is_none() {
-                return Err(chumsky::error::Rich::custom(
-                    span,
-                    "normal path item requires selector or index",
-                ));
-            }
-            if relation == Relation::Field && !filters.is_empty() {
-                return Err(chumsky::error::Rich::custom(
-                    span,
-                    "field relation cannot have filters",
-                ));
-            }
-            Ok(PathItem::Normal(NormalPathItem {
-                relation,
-                selector,
-                index,
-                filters,
-            }))
-        })
+        .try_map(
+            |(((relation, selector), index), (filters, capture)), span| {
+                if selector.is_none() && index.is_none() {
+                    return Err(chumsky::error::Rich::custom(
+                        span,
+                        "normal path item requires selector or index",
+                    ));
+                }
+                if relation == Relation::Field && !filters.is_empty() {
+                    return Err(chumsky::error::Rich::custom(
+                        span,
+                        "field relation cannot have filters",
+                    ));
+                }
+                Ok(PathItem::Normal(NormalPathItem {
+                    relation,
+                    selector,
+                    index,
+                    filters,
+                    capture,
+                }))
+            },
+        )
         .labelled("normal path item");
 
+    // a word operation is in operator position, and not a field name, when
+    // whitespace and something other than an operator or a closing follow,
+    // as in `[/image contains 'x']` but not in `[contains]`
+    let operand_ahead = ws1().then(one_of("=!<>~&|)]").not()).rewind().or_not();
     let field_shorthand_path_item = identifier_slice_parser()
+        .then(operand_ahead)
+        .try_map(|(name, operand_ahead), span| {
+            if operand_ahead.is_some() && WORD_OPERATIONS.contains(&name) {
+                Err(chumsky::error::Rich::custom(
+                    span,
+                    format!("`{}` is an operation here", name),
+                ))
+            } else {
+                Ok(name)
+            }
+        })
         .map(|name| {
             PathItem::Normal(NormalPathItem {
                 relation: Relation::Field,
                 selector: Some(Selector::from(name)),
                 index: None,
                 filters: vec![],
+                capture: None,
             })
         })
         .labelled("field shorthand path item");
 
     choice((
+        parent_path_item_parser(filter.clone()),
         elevation_path_item,
         normal_path_item,
         field_shorthand_path_item,
@@ -338,6 +305,264 @@ fn path_item_parser<'a>(
     .labelled("path_item")
 }
 
+/// Parses the filters of a normal path item, with an optional `[as name]`
+/// capture among them
+fn filters_and_capture_parser<'a>(
+    filter: impl Parser<'a, &'a str, Filter<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, (Vec<Filter<'a>>, Option<&'a str>), extra::Err<ParseError<'a>>> + Clone
+{
+    let capture = just('[')
+        .ignore_then(ws())
+        .ignore_then(just("as"))
+        .ignore_then(
+            any()
+                .filter(|c: &char| c.is_whitespace())
+                .repeated()
+                .at_least(1),
+        )
+        .ignore_then(identifier_slice_parser())
+        .then_ignore(ws())
+        .then_ignore(just(']'))
+        .labelled("capture");
+    choice((capture.map(Err), filter.map(Ok)))
+        .then_ignore(ws())
+        .repeated()
+        .collect::<Vec<_>>()
+        .try_map(|items, span| {
+            let mut filters = vec![];
+            let mut capture = None;
+            for item in items {
+                match item {
+                    Ok(filter) => filters.push(filter),
+                    Err(name) if capture.is_none() => capture = Some(name),
+                    Err(_) => {
+                        return Err(chumsky::error::Rich::custom(
+                            span,
+                            "path item cannot have more than one capture",
+                        ));
+                    }
+                }
+            }
+            Ok((filters, capture))
+        })
+}
+
+fn parent_path_item_parser<'a>(
+    filter: impl Parser<'a, &'a str, Filter<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, PathItem<'a>, extra::Err<ParseError<'a>>> + Clone {
+    // `/..` must not be the start of a label such as `/..a`
+    ws().ignore_then(just("/.."))
+        .ignore_then(just("**").or_not())
+        .then_ignore(label_char_parser().not())
+        .then_ignore(ws())
+        .then(filter.repeated().collect::<Vec<_>>())
+        .then_ignore(ws())
+        .map(|(ancestors, filters)| {
+            PathItem::Parent(ParentPathItem {
+                ancestors: ancestors.is_some(),
+                filters,
+            })
+        })
+        .labelled("parent path item")
+}
+
+fn filter_parser<'a>(
+    left: impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, Filter<'a>, extra::Err<ParseError<'a>>> + Clone {
+    // precedence, from loosest to tightest: `|`, `&`, `!`
+    let expression = recursive(|expression| {
+        let type_expression = just(':')
+            .ignore_then(identifier_parser())
+            .map(|ty| Expression::Type { ty })
+            .labelled("type expression");
+        let group = just('(')
+            .ignore_then(ws())
+            .ignore_then(expression)
+            .then_ignore(ws())
+            .then_ignore(just(')'))
+            .labelled("group expression");
+        let atom = choice((
+            group,
+            type_expression,
+            call_expression_parser(left.clone()),
+            user_call_expression_parser(left.clone()),
+            ternary_expression_parser(left),
+        ));
+        let not = just('!')
+            .then_ignore(just('=').not())
+            .then_ignore(ws())
+            .repeated()
+            .foldr(atom, |_, expr| Expression::Not {
+                expression: Box::new(expr),
+            })
+            .labelled("not expression");
+        let and = not
+            .separated_by(ws().then(just('&')).then(ws()))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map(|expressions: Vec<Expression<'_>>| {
+                if expressions.len() == 1 {
+                    expressions.into_iter().next().unwrap()
+                } else {
+                    Expression::And { expressions }
+                }
+            })
+            .labelled("and expression");
+        and.separated_by(ws().then(just('|')).then(ws()))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map(|expressions: Vec<Expression<'_>>| {
+                if expressions.len() == 1 {
+                    expressions.into_iter().next().unwrap()
+                } else {
+                    Expression::Or { expressions }
+                }
+            })
+            .labelled("expression")
+    });
+    just('[')
+        .ignore_then(ws())
+        .ignore_then(expression)
+        .then_ignore(ws())
+        .then_ignore(just(']'))
+        .map(|expr| Filter { expr })
+        .labelled("filter")
+}
+
+fn ternary_expression_parser<'a>(
+    left: impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, Expression<'a>, extra::Err<ParseError<'a>>> + Clone {
+    left.clone()
+        .then(comparison_parser(left).or_not())
+        .try_map(|(left, op_right), span| {
+            if left.0.is_empty() && op_right.is_none() {
+                return Err(chumsky::error::Rich::custom(
+                    span,
+                    "empty ternary expression",
+                ));
+            }
+            Ok(Expression::Ternary { left, op_right })
+        })
+        .labelled("ternary expression")
+}
+
+fn call_expression_parser<'a>(
+    arg: impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, Expression<'a>, extra::Err<ParseError<'a>>> + Clone {
+    let function = choice((
+        just("count").to(Function::Count),
+        just("len").to(Function::Len),
+        just("sum").to(Function::Sum),
+        just("min").to(Function::Min),
+        just("max").to(Function::Max),
+        just("avg").to(Function::Avg),
+    ))
+    .labelled("function");
+    function
+        .then_ignore(just('('))
+        .then_ignore(ws())
+        .then(arg.clone().try_map(|arg, span| {
+            if arg.0.is_empty() {
+                Err(chumsky::error::Rich::custom(
+                    span,
+                    "missing function argument",
+                ))
+            } else {
+                Ok(arg)
+            }
+        }))
+        .then_ignore(ws())
+        .then_ignore(just(')'))
+        .then(comparison_parser(arg).or_not())
+        .map(|((function, arg), op_right)| Expression::Call {
+            function,
+            arg,
+            op_right,
+        })
+        .labelled("call expression")
+}
+
+// a call of a function defined with `fn`, with relative or variable paths
+// as arguments, e.g. `[is_public(/ports, $defaults)]`; `.` is the filtered cell
+fn user_call_expression_parser<'a>(
+    path: impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, Expression<'a>, extra::Err<ParseError<'a>>> + Clone {
+    let var_arg = just('$')
+        .ignore_then(identifier_parser())
+        .then(path.clone())
+        .map(|(name, path)| Operand::Var(name, path));
+    let path_arg = relation_parser()
+        .rewind()
+        .ignore_then(path.clone())
+        .map(Operand::Path);
+    let self_arg = just('.').to(Operand::Path(Path(vec![])));
+    // the builtin functions keep their own argument rules
+    let name = identifier_parser().try_map(|name, span| {
+        if matches!(
+            name.as_str(),
+            "count" | "len" | "sum" | "min" | "max" | "avg"
+        ) {
+            Err(chumsky::error::Rich::custom(
+                span,
+                format!("bad arguments of {}()", name),
+            ))
+        } else {
+            Ok(name)
+        }
+    });
+    name.then(
+        choice((var_arg, path_arg, self_arg))
+            .padded_by(ws())
+            .separated_by(just(','))
+            .collect::<Vec<_>>()
+            .delimited_by(just('('), just(')')),
+    )
+    .then(comparison_parser(path).or_not())
+    .map(|((name, args), op_right)| Expression::UserCall {
+        name,
+        args,
+        op_right,
+    })
+    .labelled("user call expression")
+}
+
+/// Parses a comparison operator and its right side: a literal value, a
+/// `$var/path` or a path relative to the filtered cell
+fn comparison_parser<'a>(
+    path: impl Parser<'a, &'a str, Path<'a>, extra::Err<ParseError<'a>>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, (&'a str, Operand<'a>), extra::Err<ParseError<'a>>> + Clone {
+    let var_operand = just('$')
+        .ignore_then(identifier_parser())
+        .then(path.clone())
+        .map(|(name, path)| Operand::Var(name, path));
+    let path_operand = relation_parser()
+        .rewind()
+        .ignore_then(path)
+        .map(Operand::Path);
+    ws().ignore_then(operation_parser())
+        .then_ignore(ws())
+        .then(choice((
+            var_operand,
+            rvalue_parser().map(Operand::Value),
+            path_operand,
+        )))
+        .try_map(|(op, right), span| {
+            if op != "~=" {
+                return Ok((op, right));
+            }
+            let Operand::Value(OwnValue::String(pattern)) = right else {
+                return Err(chumsky::error::Rich::custom(
+                    span,
+                    "regex operand must be a string",
+                ));
+            };
+            let regex = regex::Regex::new(&pattern)
+                .map_err(|e| chumsky::error::Rich::custom(span, format!("bad regex: {}", e)))?;
+            Ok((op, Operand::Regex(FilterRegex(regex))))
+        })
+        .labelled("comparison")
+}
+
 #[cfg(test)]
 #[test]
 fn test_parse_string() {
@@ -404,6 +629,19 @@ fn test_parse_file_path_start_variants() {
             .into_result(),
         Ok(PathStart::Var("x".to_string()))
     );
+    assert_eq!(
+        path_start_parser()
+            .then_ignore(end())
+            .parse("f($x, ./y)")
+            .into_result(),
+        Ok(PathStart::Call(
+            "f".to_string(),
+            vec![
+                (PathStart::Var("x".to_string()), Path(vec![])),
+                (PathStart::File("./y".to_string()), Path(vec![]))
+            ]
+        ))
+    );
 }
 
 fn unescape_string(s: &str, special: char) -> String {
@@ -432,6 +670,61 @@ fn ws<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>>
         .ignored()
 }
 
+fn ws1<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>> + Clone {
+    any()
+        .filter(|c: &char| c.is_whitespace() && !matches!(*c, '\n' | '\r'))
+        .repeated()
+        .at_least(1)
+        .ignored()
+}
+
+// a label is made of path code points and the `?` glob wildcard
+fn label_char_parser<'src>()
+-> impl Parser<'src, &'src str, char, extra::Err<ParseError<'src>>> + Clone {
+    choice((path_code_point(), just('?')))
+}
+
+fn path_item_selector_parser<'src>()
+-> impl Parser<'src, &'src str, Selector<'src>, extra::Err<ParseError<'src>>> + Clone {
+    let label_char = label_char_parser();
+    // a glob character class is part of the label only if more label
+    // characters follow it, otherwise it is parsed as an index or filter
+    let class = just('[')
+        .then(none_of("[]").repeated().at_least(1))
+        .then(just(']'))
+        .then(choice((label_char.clone(), one_of(",}"))).rewind())
+        .ignored();
+    let label = choice((label_char.ignored(), class))
+        .repeated()
+        .at_least(1)
+        .to_slice();
+    let alternatives = just('{')
+        .ignore_then(
+            label
+                .clone()
+                .separated_by(just(',').padded_by(ws()))
+                .at_least(1)
+                .to_slice()
+                .padded_by(ws()),
+        )
+        .then_ignore(just('}'))
+        .map(Selector::Alt);
+    choice((alternatives, label.map(Selector::from))).labelled("path_item_selector")
+}
+
+fn path_item_index_parser<'src>()
+-> impl Parser<'src, &'src str, Index, extra::Err<ParseError<'src>>> + Clone {
+    let range = number_isize_parser()
+        .or_not()
+        .then_ignore(just(".."))
+        .then(number_isize_parser().or_not())
+        .map(|(start, end)| Index::Range(start, end));
+    just('[')
+        .ignore_then(choice((range, number_isize_parser().map(Index::At))))
+        .then_ignore(just(']'))
+        .labelled("path_item_index")
+}
+
 fn identifier_slice_parser<'src>()
 -> impl Parser<'src, &'src str, &'src str, extra::Err<ParseError<'src>>> + Clone {
     any()
@@ -498,9 +791,35 @@ fn string_parser<'src>()
     choice((quoted_parser('\''), quoted_parser('"'))).labelled("string")
 }
 
+// operations which are also valid field names
+const WORD_OPERATIONS: [&str; 3] = ["startswith", "endswith", "contains"];
+
 fn operation_parser<'src>()
 -> impl Parser<'src, &'src str, &'src str, extra::Err<ParseError<'src>>> + Clone {
-    choice((just("==").to("=="), just("!=").to("!="))).labelled("operation")
+    // a word operation must end at a word boundary, so `containsx` is not
+    // the `contains` operation
+    let word = |w: &'static str| {
+        just(w)
+            .then_ignore(
+                any()
+                    .filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                    .not(),
+            )
+            .to(w)
+    };
+    choice((
+        just("==").to("=="),
+        just("!=").to("!="),
+        just("<=").to("<="),
+        just(">=").to(">="),
+        just("<").to("<"),
+        just(">").to(">"),
+        just("~=").to("~="),
+        word("startswith"),
+        word("endswith"),
+        word("contains"),
+    ))
+    .labelled("operation")
 }
 
 pub(super) fn value_int_parser<'src>()
@@ -517,10 +836,38 @@ fn value_string_parser<'src>()
         .labelled("value_string")
 }
 
+fn value_float_parser<'src>()
+-> impl Parser<'src, &'src str, OwnValue, extra::Err<ParseError<'src>>> + Clone {
+    let digits = any()
+        .filter(|c: &char| c.is_ascii_digit())
+        .repeated()
+        .at_least(1);
+    one_of("+-")
+        .or_not()
+        .then(digits)
+        .then(just('.'))
+        .then(digits)
+        .to_slice()
+        .try_map(|raw: &str, span| {
+            raw.parse::<f64>()
+                .map(|f| OwnValue::Float(StrFloat(f)))
+                .map_err(|e| {
+                    chumsky::error::Rich::custom(span, format!("bad float {}: {}", raw, e))
+                })
+        })
+        .labelled("value_float")
+}
+
+// `true`, `false` and `null` are keywords, other identifiers are strings
 fn value_ident_parser<'src>()
 -> impl Parser<'src, &'src str, OwnValue, extra::Err<ParseError<'src>>> + Clone {
     identifier_parser()
-        .map(OwnValue::String)
+        .map(|ident| match ident.as_str() {
+            "true" => OwnValue::Bool(true),
+            "false" => OwnValue::Bool(false),
+            "null" => OwnValue::None,
+            _ => OwnValue::String(ident),
+        })
         .labelled("value_ident")
 }
 
@@ -528,12 +875,171 @@ pub(super) fn rvalue_parser<'src>()
 -> impl Parser<'src, &'src str, OwnValue, extra::Err<ParseError<'src>>> + Clone {
     choice((
         value_string_parser(),
+        value_float_parser(),
         value_int_parser(),
         value_ident_parser(),
     ))
     .labelled("value")
 }
 
+/// Parses an assignment value: a scalar or a json-like array or object
+/// literal, e.g. `["80:80", "443:443"]` or `{cpu: 2, "mem": "1Gi"}`
+pub(super) fn structured_rvalue_parser<'src>()
+-> impl Parser<'src, &'src str, RValue<'src>, extra::Err<ParseError<'src>>> + Clone {
+    let ws = any()
+        .filter(|c: &char| c.is_whitespace())
+        .repeated()
+        .ignored();
+    recursive(|rvalue| {
+        let array = rvalue
+            .clone()
+            .padded_by(ws)
+            .separated_by(just(','))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .padded_by(ws)
+            .delimited_by(just('['), just(']'))
+            .map(RValue::Array)
+            .labelled("array");
+        let entry = choice((string_parser(), identifier_parser()))
+            .padded_by(ws)
+            .then_ignore(just(':'))
+            .then(rvalue.padded_by(ws));
+        let object = entry
+            .separated_by(just(','))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .padded_by(ws)
+            .delimited_by(just('{'), just('}'))
+            .map(RValue::Object)
+            .labelled("object");
+        choice((array, object, rvalue_parser().map(RValue::Value)))
+    })
+    .labelled("value")
+}
+
+/// Parses an arithmetic expression of scalar values and paths with a start,
+/// e.g. `$cfg/replicas * 2 + 1` or `"v"+./version.txt^text/[0]`. Since `/`
+/// and `-` also appear in paths and labels, an operator must be surrounded by
+/// whitespace, unless it is next to a quoted string or a parenthesised
+/// expression: `$f/n/2` is a path and `2024-01-01` a string.
+pub(super) fn expression_parser<'src>()
+-> impl Parser<'src, &'src str, RValue<'src>, extra::Err<ParseError<'src>>> + Clone {
+    let delimited_start = one_of("\"'(");
+    // a bare literal is a value only if an operator or the end of the
+    // statement follows, so that `dev/x` stays a path
+    let literal_end = choice((
+        ws().then(choice((end(), one_of(";}\n\r)").ignored())))
+            .ignored(),
+        ws1().then(one_of("+-*/")).then(ws1()).ignored(),
+        ws().then(one_of("+-*/"))
+            .then(ws())
+            .then(delimited_start)
+            .ignored(),
+    ))
+    .rewind();
+    recursive(|expr| {
+        // each operand comes with whether it is quoted or parenthesised
+        let operand = choice((
+            value_string_parser().map(|v| (RValue::Value(v), true)),
+            expr.padded_by(ws())
+                .delimited_by(just('('), just(')'))
+                .map(|(e, _)| (e, true)),
+            choice((
+                value_float_parser().then_ignore(literal_end.clone()),
+                value_int_parser().then_ignore(literal_end.clone()),
+                value_ident_parser().then_ignore(literal_end),
+            ))
+            .map(|v| (RValue::Value(v), false)),
+            path_with_starter_parser()
+                .nested_in(path_operand_text_parser())
+                .map(|(start, path)| (RValue::Path(start, path), false)),
+        ));
+        binary_parser(binary_parser(operand, "*/"), "+-")
+    })
+    .map(|(expr, _)| expr)
+    .labelled("expression")
+}
+
+// a left associative chain of operands joined by the operators in `ops`
+fn binary_parser<'src>(
+    operand: impl Parser<'src, &'src str, (RValue<'src>, bool), extra::Err<ParseError<'src>>> + Clone,
+    ops: &'static str,
+) -> impl Parser<'src, &'src str, (RValue<'src>, bool), extra::Err<ParseError<'src>>> + Clone {
+    let op = || {
+        one_of(ops).map(|c| match c {
+            '+' => BinaryOp::Add,
+            '-' => BinaryOp::Sub,
+            '*' => BinaryOp::Mul,
+            _ => BinaryOp::Div,
+        })
+    };
+    // the operator, and whether it is surrounded by whitespace
+    let spaced_op = choice((
+        ws1()
+            .ignore_then(op())
+            .then_ignore(ws1())
+            .map(|op| (op, true)),
+        ws().ignore_then(op())
+            .then_ignore(ws())
+            .map(|op| (op, false)),
+    ));
+    operand
+        .clone()
+        .then(spaced_op.then(operand).repeated().collect::<Vec<_>>())
+        .try_map(|(first, rest), span| {
+            let (mut left, mut left_delimited) = first;
+            for ((op, spaced), (right, right_delimited)) in rest {
+                if !spaced && !left_delimited && !right_delimited {
+                    return Err(chumsky::error::Rich::custom(
+                        span,
+                        format!("put whitespace around the operator `{}`", op),
+                    ));
+                }
+                left = RValue::Binary(Box::new(left), op, Box::new(right));
+                left_delimited = false;
+            }
+            Ok((left, left_delimited))
+        })
+}
+
+// the text of a path operand: it ends at whitespace which is not followed by
+// a path item, or at an operator followed by a quoted or parenthesised operand
+fn path_operand_text_parser<'src>()
+-> impl Parser<'src, &'src str, &'src str, extra::Err<ParseError<'src>>> + Clone {
+    let quoted = |q: char| quoted_parser(q).ignored();
+    // brackets, parentheses and braces are taken whole, with the strings in them
+    let group = recursive(|group| {
+        let inner = choice((
+            quoted('"'),
+            quoted('\''),
+            group,
+            none_of("[](){}\"'").ignored(),
+        ))
+        .repeated();
+        choice((
+            inner.clone().delimited_by(just('['), just(']')),
+            inner.clone().delimited_by(just('('), just(')')),
+            inner.delimited_by(just('{'), just('}')),
+        ))
+    });
+    let path_ws = ws1()
+        .then(choice((
+            one_of("@#^[").ignored(),
+            just('/').then(ws1().not()).ignored(),
+        )))
+        .rewind()
+        .then(ws1());
+    let plain = one_of("+-*/")
+        .then(one_of("\"'("))
+        .not()
+        .ignore_then(any().filter(|c: &char| !c.is_whitespace() && !"[](){};\"'".contains(*c)));
+    choice((group, path_ws.ignored(), plain.ignored()))
+        .repeated()
+        .at_least(1)
+        .to_slice()
+}
+
 fn relation_parser<'src>()
 -> impl Parser<'src, &'src str, Relation, extra::Err<ParseError<'src>>> + Clone {
     let rels = [
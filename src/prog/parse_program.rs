@@ -3,33 +3,13 @@ use crate::{
     prog::{parse_path::*, program::*, *},
 };
 use chumsky::prelude::*;
+use std::rc::Rc;
 
 pub fn parse_program(input: &str) -> Res<Program<'_>> {
-    let var_bind = just('$')
-        .ignore_then(identifier_parser())
-        .then_ignore(ws())
-        .then_ignore(just(":="))
-        .then_ignore(ws())
-        .then(path_with_starter_parser())
-        .map(|(name, (start, path))| Statement::VarBind(name, start, path));
-
-    let assignment = path_with_starter_parser()
-        .then_ignore(ws())
-        .then_ignore(just('='))
-        .then_ignore(ws())
-        .then(rvalue_parser())
-        .map(|((start, path), value)| Statement::Assignment(start, path, value));
-
-    let path_stmt = path_with_starter_parser().map(|(start, path)| Statement::Path(start, path));
-
-    let statement_sep = choice((
-        ws().ignore_then(just(';')).then_ignore(ws()).ignored(),
-        line_ws(),
-    ));
-
-    let program = choice((var_bind, assignment, path_stmt))
-        .labelled("statement")
-        .separated_by(statement_sep)
+    // leading whitespace is skipped here, to keep the positions in errors
+    let program = ws()
+        .ignore_then(statement_parser())
+        .separated_by(statement_sep())
         .allow_trailing()
         .collect::<Vec<_>>()
         .map(Program)
@@ -42,6 +22,253 @@ pub fn parse_program(input: &str) -> Res<Program<'_>> {
         .map_err(|err| inputerr(convert_error(input, err, "<program>")))
 }
 
+fn statement_parser<'src>()
+-> impl Parser<'src, &'src str, Statement<'src>, extra::Err<ParseError<'src>>> + Clone {
+    recursive(|statement| {
+        let block = statement
+            .clone()
+            .separated_by(statement_sep())
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .padded_by(ws())
+            .delimited_by(just('{'), just('}'))
+            .labelled("block");
+
+        let for_loop = just("for")
+            .ignore_then(ws1())
+            .ignore_then(just('$'))
+            .ignore_then(identifier_parser())
+            .then_ignore(ws1())
+            .then_ignore(just("in"))
+            .then_ignore(ws1())
+            .then(path_with_starter_parser())
+            .then_ignore(ws())
+            .then(block.clone())
+            .map(|((name, (start, path)), body)| Statement::For(name, start, path, body));
+
+        // `else if` is an else block with a single if statement
+        let else_branch = ws()
+            .ignore_then(just("else"))
+            .ignore_then(ws())
+            .ignore_then(choice((
+                block.clone(),
+                just("if")
+                    .then(ws1())
+                    .rewind()
+                    .ignore_then(statement)
+                    .map(|statement| vec![statement]),
+            )));
+        let if_else = just("if")
+            .ignore_then(ws1())
+            .ignore_then(path_with_starter_parser())
+            .then_ignore(ws())
+            .then(block.clone())
+            .then(else_branch.or_not())
+            .map(|(((start, path), then_body), else_body)| {
+                Statement::If(start, path, then_body, else_body.unwrap_or_default())
+            });
+
+        let params = just('$')
+            .ignore_then(identifier_parser())
+            .padded_by(ws())
+            .separated_by(just(','))
+            .collect::<Vec<_>>()
+            .delimited_by(just('('), just(')'));
+        let fn_def = just("fn")
+            .ignore_then(ws1())
+            .ignore_then(identifier_parser())
+            .then_ignore(ws())
+            .then(params)
+            .then_ignore(ws())
+            .then(choice((
+                just(":=")
+                    .ignore_then(ws())
+                    .ignore_then(path_with_starter_parser())
+                    .map(|(start, path)| FunctionBody::Path(start, path)),
+                block.map(FunctionBody::Block),
+            )))
+            .map(|((name, params), body)| {
+                Statement::FnDef(Rc::new(FunctionDef { name, params, body }))
+            });
+
+        let var_bind = just('$')
+            .ignore_then(identifier_parser())
+            .then_ignore(ws())
+            .then_ignore(just(":="))
+            .then_ignore(ws())
+            .then(path_with_starter_parser())
+            .map(|(name, (start, path))| Statement::VarBind(name, start, path));
+
+        let assignment = path_with_starter_parser()
+            .then_ignore(ws())
+            .then_ignore(just('='))
+            .then_ignore(ws())
+            .then(rhs_parser())
+            .map(|((start, path), value)| Statement::Assignment(start, path, value));
+
+        let compound_assignment = path_with_starter_parser()
+            .then_ignore(ws())
+            .then(choice((
+                just("+=").to(BinaryOp::Add),
+                just("-=").to(BinaryOp::Sub),
+                just("*=").to(BinaryOp::Mul),
+                just("/=").to(BinaryOp::Div),
+            )))
+            .then_ignore(ws())
+            .then(expression_parser())
+            .map(|(((start, path), op), value)| {
+                Statement::CompoundAssignment(start, path, op, value)
+            });
+
+        let create = just("create")
+            .ignore_then(ws1())
+            .ignore_then(path_with_starter_parser())
+            .then_ignore(ws())
+            .then_ignore(just('='))
+            .then_ignore(ws())
+            .then(rhs_parser())
+            .map(|((start, path), value)| Statement::Create(start, path, value));
+
+        let diff_args = || {
+            just("diff")
+                .ignore_then(ws1())
+                .ignore_then(path_with_starter_parser())
+                .then_ignore(ws())
+                .then(path_with_starter_parser())
+        };
+        let diff = diff_args().map(|((left_start, left_path), (right_start, right_path))| {
+            Statement::Diff(left_start, left_path, right_start, right_path)
+        });
+        let diff_bind = just('$')
+            .ignore_then(identifier_parser())
+            .then_ignore(ws())
+            .then_ignore(just(":="))
+            .then_ignore(ws())
+            .then(diff_args())
+            .map(
+                |(name, ((left_start, left_path), (right_start, right_path)))| {
+                    Statement::DiffBind(name, left_start, left_path, right_start, right_path)
+                },
+            );
+
+        let tree = just("tree")
+            .ignore_then(ws1())
+            .ignore_then(path_with_starter_parser())
+            .map(|(start, path)| Statement::Tree(start, path));
+
+        let copy = just("copy")
+            .ignore_then(ws1())
+            .ignore_then(path_with_starter_parser())
+            .then_ignore(ws())
+            .then(path_with_starter_parser())
+            .map(|((src_start, src_path), (dst_start, dst_path))| {
+                Statement::Copy(src_start, src_path, dst_start, dst_path)
+            });
+
+        let delete = just("delete")
+            .ignore_then(ws1())
+            .ignore_then(path_with_starter_parser())
+            .map(|(start, path)| Statement::Delete(start, path));
+
+        // the empty file path is a valid path start, but not a statement
+        let path_stmt = path_with_starter_parser()
+            .filter(|(start, path)| {
+                !(path.0.is_empty() && matches!(start, PathStart::File(file) if file.is_empty()))
+            })
+            .map(|(start, path)| Statement::Path(start, path));
+
+        choice((
+            fn_def,
+            for_loop,
+            if_else,
+            diff_bind,
+            var_bind,
+            tree,
+            copy,
+            delete,
+            create,
+            diff,
+            compound_assignment,
+            assignment,
+            path_stmt,
+        ))
+        .labelled("statement")
+    })
+}
+
+/// Replaces the `#` comments with spaces, so that the positions in error
+/// messages stay the same. A comment starts with a `#` at the beginning of a
+/// line, or with a `#` between whitespace (`#label` and the like are path
+/// items), outside of quoted strings, and ends with the line.
+pub(super) fn blank_comments(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut in_comment = false;
+    let mut line_start = true;
+    let mut after_ws = true;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            in_comment = false;
+            (line_start, after_ws) = (true, true);
+            output.push(c);
+            continue;
+        }
+        if in_comment {
+            output.push(' ');
+            continue;
+        }
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            output.push(c);
+            continue;
+        }
+        let before_ws = chars.peek().is_none_or(|next| next.is_whitespace());
+        if c == '#' && (line_start || (after_ws && before_ws)) {
+            in_comment = true;
+            output.push(' ');
+            continue;
+        }
+        if matches!(c, '\'' | '"' | '`') {
+            quote = Some(c);
+        }
+        line_start &= c.is_whitespace();
+        after_ws = c.is_whitespace();
+        output.push(c);
+    }
+    output
+}
+
+fn statement_sep<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>> + Clone {
+    choice((
+        ws().ignore_then(just(';')).then_ignore(ws()).ignored(),
+        line_ws(),
+    ))
+}
+
+/// The right side of an assignment: a structured literal if it ends the
+/// statement, otherwise an expression of values and paths with a start
+/// (`./other.json^json/a`, `$x/a + 1`)
+fn rhs_parser<'src>()
+-> impl Parser<'src, &'src str, RValue<'src>, extra::Err<ParseError<'src>>> + Clone {
+    let statement_end = any()
+        .filter(|c: &char| c.is_whitespace() && !matches!(*c, '\n' | '\r'))
+        .repeated()
+        .then(choice((end(), one_of(";}\n\r").ignored())))
+        .rewind();
+    choice((
+        structured_rvalue_parser().then_ignore(statement_end),
+        expression_parser(),
+    ))
+}
+
 fn ws<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>> + Clone {
     any()
         .filter(|c: &char| c.is_whitespace())
@@ -49,21 +276,21 @@ fn ws<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>>
         .ignored()
 }
 
+fn ws1<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>> + Clone {
+    any()
+        .filter(|c: &char| c.is_whitespace())
+        .repeated()
+        .at_least(1)
+        .ignored()
+}
+
 fn line_ws<'src>() -> impl Parser<'src, &'src str, (), extra::Err<ParseError<'src>>> + Clone {
     any()
         .filter(|c: &char| c.is_whitespace() && !matches!(*c, '\n' | '\r'))
         .repeated()
-        .ignore_then(
-            any()
-                .filter(|c: &char| matches!(*c, '\n' | '\r'))
-                .repeated()
-                .at_least(1),
-        )
-        .then_ignore(
-            any()
-                .filter(|c: &char| c.is_whitespace() && !matches!(*c, '\n' | '\r'))
-                .repeated(),
-        )
+        .ignore_then(any().filter(|c: &char| matches!(*c, '\n' | '\r')))
+        // the following lines can be empty or blank
+        .then_ignore(ws())
         .ignored()
 }
 
@@ -173,10 +173,15 @@ fn alpha1<'src>() -> impl Parser<'src, &'src str, &'src str, extra::Err<ParseErr
 
 pub(super) fn path_code_points<'src>()
 -> impl Parser<'src, &'src str, &'src str, extra::Err<ParseError<'src>>> + Clone {
+    path_code_point().repeated().at_least(1).to_slice()
+}
+
+pub(super) fn path_code_point<'src>()
+-> impl Parser<'src, &'src str, char, extra::Err<ParseError<'src>>> + Clone {
     let accept = |c: &char| {
         matches!(*c, '+' | '-' | '_' | '.' | ':' | '*' | '$') || c.is_ascii_alphanumeric()
     };
-    any().filter(accept).repeated().at_least(1).to_slice()
+    any().filter(accept)
 }
 
 pub(super) fn url_code_points<'src>()
@@ -1,4 +1,9 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    io::Read,
+    ops::Range,
+    sync::OnceLock,
+};
 
 use crate::{
     api::*,
@@ -14,12 +19,25 @@ pub enum PathStart<'a> {
     File(String),
     String(String),
     Var(String),
+    // a call of a path function defined with `fn`, e.g. `images(./compose.yaml)`
+    Call(String, Vec<(PathStart<'a>, Path<'a>)>),
+    // `-`, the standard input as a string, e.g. `-^json/items`
+    Stdin,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum PathItem<'a> {
     Elevation(ElevationPathItem<'a>),
     Normal(NormalPathItem<'a>),
+    Parent(ParentPathItem<'a>),
+}
+
+/// `/..` goes to the parent cell, `/..**` to all the ancestors (nearest first).
+/// The parent of a domain root is the cell it was interpreted from.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ParentPathItem<'a> {
+    pub(crate) ancestors: bool,
+    pub(crate) filters: Vec<Filter<'a>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -38,8 +56,18 @@ pub(crate) struct InterpretationParam {
 pub(crate) struct NormalPathItem<'a> {
     pub(crate) relation: Relation,
     pub(crate) selector: Option<Selector<'a>>, // field name (string) or '*' or '**'
-    pub(crate) index: Option<isize>,
+    pub(crate) index: Option<Index>,
     pub(crate) filters: Vec<Filter<'a>>, // [@size>0] or [.name.endswith('.rs')]
+    pub(crate) capture: Option<&'a str>, // [as name], binds the matched cell to `name`
+}
+
+/// Index of a path item, either a single position (`[2]`, `[-1]`) or a
+/// python-style slice (`[2..5]`, `[..10]`, `[-3..]`) with an exclusive end.
+/// Negative positions count from the end of the group.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Index {
+    At(isize),
+    Range(Option<isize>, Option<isize>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -51,7 +79,7 @@ pub(crate) struct Filter<'a> {
 pub(crate) enum Expression<'a> {
     Ternary {
         left: Path<'a>,
-        op_right: Option<(&'a str, OwnValue)>,
+        op_right: Option<(&'a str, Operand<'a>)>,
     },
     Type {
         ty: String,
@@ -59,6 +87,65 @@ pub(crate) enum Expression<'a> {
     Or {
         expressions: Vec<Expression<'a>>,
     },
+    And {
+        expressions: Vec<Expression<'a>>,
+    },
+    Not {
+        expression: Box<Expression<'a>>,
+    },
+    Call {
+        function: Function,
+        arg: Path<'a>,
+        op_right: Option<(&'a str, Operand<'a>)>,
+    },
+    // a call of a path function defined with `fn`, true if it has any match;
+    // the arguments are relative (`/x`, `.`) or variable (`$v/x`) paths
+    UserCall {
+        name: String,
+        args: Vec<Operand<'a>>,
+        op_right: Option<(&'a str, Operand<'a>)>,
+    },
+}
+
+/// Functions which can be called in filter expressions, e.g. `[count(/x)>2]`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Function {
+    // number of cells matched by the argument
+    Count,
+    // length of the first value matched by the argument
+    Len,
+    // aggregates of the numeric values matched by the argument
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+/// The right side of a filter comparison
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Operand<'a> {
+    Value(OwnValue),
+    // compiled once when the filter is parsed, used by the `~=` operator
+    Regex(FilterRegex),
+    // a path relative to the filtered cell, e.g. `[/image == /default_image]`
+    Path(Path<'a>),
+    // a path starting from a variable, e.g. `[/image == $release/image]`
+    Var(String, Path<'a>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FilterRegex(pub(crate) regex::Regex);
+
+impl PartialEq for FilterRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl From<OwnValue> for Operand<'_> {
+    fn from(value: OwnValue) -> Self {
+        Operand::Value(value)
+    }
 }
 
 impl Display for Path<'_> {
@@ -79,6 +166,22 @@ impl Display for PathItem<'_> {
             PathItem::Normal(n) => {
                 write!(f, "{}", n)?;
             }
+            PathItem::Parent(p) => {
+                write!(f, "{}", p)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for ParentPathItem<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/..")?;
+        if self.ancestors {
+            write!(f, "**")?;
+        }
+        for filter in &self.filters {
+            write!(f, "{}", filter)?;
         }
         Ok(())
     }
@@ -113,10 +216,48 @@ impl Display for NormalPathItem<'_> {
         for filter in &self.filters {
             write!(f, "{}", filter)?;
         }
+        if let Some(name) = self.capture {
+            write!(f, "[as {}]", name)?;
+        }
         Ok(())
     }
 }
 
+impl Index {
+    /// Resolves a slice against the length of a group, clamping the bounds
+    /// to the group. Returns an empty range if the slice selects nothing.
+    pub(crate) fn range(start: Option<isize>, end: Option<isize>, len: usize) -> Range<usize> {
+        let resolve = |i: isize| {
+            if i < 0 {
+                len.saturating_sub(i.unsigned_abs())
+            } else {
+                (i as usize).min(len)
+            }
+        };
+        let start = start.map_or(0, resolve);
+        let end = end.map_or(len, resolve);
+        start..end.max(start)
+    }
+}
+
+impl Display for Index {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Index::At(i) => write!(f, "{}", i),
+            Index::Range(start, end) => {
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, "..")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 impl Display for PathStart<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match &self {
@@ -124,6 +265,17 @@ impl Display for PathStart<'_> {
             PathStart::File(x) => write!(f, "{}", x)?,
             PathStart::String(x) => write!(f, "'{}'", x)?,
             PathStart::Var(x) => write!(f, "${}", x)?,
+            PathStart::Stdin => write!(f, "-")?,
+            PathStart::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (i, (start, path)) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}{}", start, path)?;
+                }
+                write!(f, ")")?;
+            }
         }
         Ok(())
     }
@@ -142,7 +294,7 @@ impl Display for Expression<'_> {
             Expression::Ternary { left, op_right } => {
                 write!(f, "{}", left)?;
                 if let Some(op_r) = op_right {
-                    write!(f, "{}{:?}", op_r.0, op_r.1)?;
+                    write!(f, "{}{}", op_r.0, op_r.1)?;
                 }
             }
             Expression::Type { ty } => {
@@ -156,11 +308,85 @@ impl Display for Expression<'_> {
                     }
                 }
             }
+            Expression::And { expressions } => {
+                for (i, expr) in expressions.iter().enumerate() {
+                    if matches!(expr, Expression::Or { .. }) {
+                        write!(f, "({})", expr)?;
+                    } else {
+                        write!(f, "{}", expr)?;
+                    }
+                    if i < expressions.len() - 1 {
+                        write!(f, "&")?;
+                    }
+                }
+            }
+            Expression::Call {
+                function,
+                arg,
+                op_right,
+            } => {
+                write!(f, "{}({})", function, arg)?;
+                if let Some(op_r) = op_right {
+                    write!(f, "{}{}", op_r.0, op_r.1)?;
+                }
+            }
+            Expression::UserCall {
+                name,
+                args,
+                op_right,
+            } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match arg {
+                        Operand::Path(path) if path.0.is_empty() => write!(f, ".")?,
+                        _ => write!(f, "{}", arg)?,
+                    }
+                }
+                write!(f, ")")?;
+                if let Some(op_r) = op_right {
+                    write!(f, "{}{}", op_r.0, op_r.1)?;
+                }
+            }
+            Expression::Not { expression } => {
+                if matches!(**expression, Expression::Or { .. } | Expression::And { .. }) {
+                    write!(f, "!({})", expression)?;
+                } else {
+                    write!(f, "!{}", expression)?;
+                }
+            }
         }
         Ok(())
     }
 }
 
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Function::Count => "count",
+            Function::Len => "len",
+            Function::Sum => "sum",
+            Function::Min => "min",
+            Function::Max => "max",
+            Function::Avg => "avg",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for Operand<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Value(v) => write!(f, "{:?}", v),
+            Operand::Regex(r) => write!(f, "{:?}", r.0.as_str()),
+            Operand::Path(path) => write!(f, "{}", path),
+            Operand::Var(name, path) => write!(f, "${}{}", name, path),
+        }
+    }
+}
+
 impl Display for InterpretationParam {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if let Some(n) = &self.name {
@@ -194,16 +420,35 @@ impl<'a, 'b: 'a> Display for DisplayPath<'a, 'b> {
     }
 }
 
+// the standard input can be read only once, so it is kept for the other
+// stdin starts of the program
+fn read_stdin() -> Res<String> {
+    static STDIN: OnceLock<Result<String, String>> = OnceLock::new();
+    let stdin = STDIN.get_or_init(|| {
+        let mut bytes = vec![];
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("cannot read stdin: {}", e))?;
+        String::from_utf8(bytes).map_err(|_| "stdin is not valid utf-8".to_string())
+    });
+    stdin.clone().map_err(ioerr)
+}
+
 impl<'a> PathStart<'a> {
     pub fn eval(&self) -> Res<Xell> {
         match self {
             PathStart::Url(s) => Xell::from(s.to_string()).be("url").err(),
             PathStart::File(s) => Xell::from(s.as_str()).be("path").be("fs").err(),
             PathStart::String(s) => Xell::from(s.as_str()).err(),
+            PathStart::Stdin => Ok(Xell::from(read_stdin()?)),
             PathStart::Var(name) => inputres(format!(
                 "cannot evaluate variable start ?{} without execution context",
                 name
             )),
+            PathStart::Call(name, _) => inputres(format!(
+                "cannot evaluate function call {}() without execution context",
+                name
+            )),
         }
     }
 }
@@ -222,4 +467,66 @@ impl<'a> Path<'a> {
     pub fn eval(self, cell: Xell) -> Searcher<'a> {
         Searcher::new(cell, self)
     }
+
+    /// The names of the variables used in the filters of this path
+    pub(crate) fn variables(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables<'p>(&'p self, names: &mut Vec<&'p str>) {
+        for item in &self.0 {
+            let filters = match item {
+                PathItem::Normal(npi) => &npi.filters,
+                PathItem::Parent(ppi) => &ppi.filters,
+                PathItem::Elevation(_) => continue,
+            };
+            for filter in filters {
+                filter.expr.collect_variables(names);
+            }
+        }
+    }
+}
+
+impl Expression<'_> {
+    fn collect_variables<'p>(&'p self, names: &mut Vec<&'p str>) {
+        let (left, op_right) = match self {
+            Expression::Ternary { left, op_right } => (Some(left), op_right),
+            Expression::Call { arg, op_right, .. } => (Some(arg), op_right),
+            Expression::UserCall { args, op_right, .. } => {
+                for arg in args {
+                    arg.collect_variables(names);
+                }
+                (None, op_right)
+            }
+            Expression::Type { .. } => (None, &None),
+            Expression::Or { expressions } | Expression::And { expressions } => {
+                for expr in expressions {
+                    expr.collect_variables(names);
+                }
+                return;
+            }
+            Expression::Not { expression } => return expression.collect_variables(names),
+        };
+        if let Some(path) = left {
+            path.collect_variables(names);
+        }
+        if let Some((_, operand)) = op_right {
+            operand.collect_variables(names);
+        }
+    }
+}
+
+impl Operand<'_> {
+    fn collect_variables<'p>(&'p self, names: &mut Vec<&'p str>) {
+        match self {
+            Operand::Var(name, path) => {
+                names.push(name);
+                path.collect_variables(names);
+            }
+            Operand::Path(path) => path.collect_variables(names),
+            _ => {}
+        }
+    }
 }
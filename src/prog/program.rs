@@ -2,11 +2,13 @@ use crate::{
     api::*,
     config::ColorPalette,
     debug,
-    pprint::pprint,
+    pprint::pprint_diff,
     prog::{searcher::Searcher, *},
 };
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 
 macro_rules! ifdebug {
     ( $body:expr ) => {
@@ -22,16 +24,61 @@ pub struct ProgramParams {
     pub print_depth: usize,
     pub print_breadth: usize,
     pub color_palette: ColorPalette,
+    pub output: OutputFormat,
+    // print at most this many matches of each path or tree statement
+    pub match_limit: Option<usize>,
+    // print the number of matches of each path or tree statement instead
+    pub count: bool,
+    // keep all writes in memory and print them as diffs instead
+    pub dry_run: bool,
 }
 
 #[derive(Clone, Debug)]
 pub enum Statement<'a> {
     Path(PathStart<'a>, Path<'a>),
-    Assignment(PathStart<'a>, Path<'a>, OwnValue),
+    Assignment(PathStart<'a>, Path<'a>, RValue<'a>),
+    // `+=` and the like, applied to the current value of each matched cell
+    CompoundAssignment(PathStart<'a>, Path<'a>, BinaryOp, RValue<'a>),
+    // assignment which creates the missing cells of the path
+    Create(PathStart<'a>, Path<'a>, RValue<'a>),
     VarBind(String, PathStart<'a>, Path<'a>),
+    Tree(PathStart<'a>, Path<'a>),
+    Copy(PathStart<'a>, Path<'a>, PathStart<'a>, Path<'a>),
+    Delete(PathStart<'a>, Path<'a>),
+    Diff(PathStart<'a>, Path<'a>, PathStart<'a>, Path<'a>),
+    DiffBind(String, PathStart<'a>, Path<'a>, PathStart<'a>, Path<'a>),
+    // runs the body once for each match, bound to the variable
+    For(String, PathStart<'a>, Path<'a>, Vec<Statement<'a>>),
+    // runs the first body if the path has any match, the second otherwise
+    If(
+        PathStart<'a>,
+        Path<'a>,
+        Vec<Statement<'a>>,
+        Vec<Statement<'a>>,
+    ),
+    FnDef(Rc<FunctionDef<'a>>),
 }
 
+/// A function defined with `fn`: a path function returns the matches of a
+/// path, a procedure runs a block of statements. The parameters are bound
+/// to the first match of each argument.
 #[derive(Clone, Debug)]
+pub struct FunctionDef<'a> {
+    pub(crate) name: String,
+    pub(crate) params: Vec<String>,
+    pub(crate) body: FunctionBody<'a>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum FunctionBody<'a> {
+    Path(PathStart<'a>, Path<'a>),
+    Block(Vec<Statement<'a>>),
+}
+
+// guards against endless recursion in function calls
+const MAX_CALL_DEPTH: usize = 64;
+
+#[derive(Debug)]
 pub struct Executor<'a> {
     program: Program<'a>,
     statement_index: usize,
@@ -39,8 +86,133 @@ pub struct Executor<'a> {
 }
 
 #[derive(Clone, Debug, Default)]
-pub struct ExecutionContext {
-    vars: HashMap<String, Xell>,
+pub struct ExecutionContext<'a> {
+    // shared with the searchers, which use the variables in filters
+    vars: Rc<HashMap<String, Xell>>,
+    functions: Rc<HashMap<String, Rc<FunctionDef<'a>>>>,
+    call_depth: usize,
+    // the number of path and tree statements run and of the cells they
+    // matched, shared with the call contexts
+    queries: Rc<std::cell::Cell<usize>>,
+    matches: Rc<std::cell::Cell<usize>>,
+    // the values of the `$var/path` filter operands of a search, which do not
+    // depend on the filtered cell, shared with its nested searches
+    operand_values: Rc<RefCell<HashMap<String, Vec<OwnValue>>>>,
+}
+
+impl<'a> ExecutionContext<'a> {
+    pub fn var(&self, name: &str) -> Option<&Xell> {
+        self.vars.get(name)
+    }
+
+    pub fn set_var(&mut self, name: impl Into<String>, cell: Xell) {
+        Rc::make_mut(&mut self.vars).insert(name.into(), cell);
+    }
+
+    /// The number of path and tree statements run in this context.
+    pub fn queries(&self) -> usize {
+        self.queries.get()
+    }
+
+    /// The number of cells matched by the path and tree statements run in
+    /// this context, within the match limit.
+    pub fn matches(&self) -> usize {
+        self.matches.get()
+    }
+
+    /// A copy of the context for a new search, without the operand values
+    /// of other searches
+    pub(crate) fn for_search(&self) -> Self {
+        let mut ctx = self.clone();
+        ctx.operand_values = Rc::default();
+        ctx
+    }
+
+    /// Returns the values of a variable filter operand, evaluating them only
+    /// the first time they are needed in a search
+    pub(crate) fn operand_values(
+        &self,
+        operand: &str,
+        eval: impl FnOnce() -> Res<Vec<OwnValue>>,
+    ) -> Res<Vec<OwnValue>> {
+        if let Some(values) = self.operand_values.borrow().get(operand) {
+            return Ok(values.clone());
+        }
+        let values = eval()?;
+        self.operand_values
+            .borrow_mut()
+            .insert(operand.to_string(), values.clone());
+        Ok(values)
+    }
+
+    fn count_query(&self, matches: usize) {
+        self.queries.set(self.queries.get() + 1);
+        self.matches.set(self.matches.get() + matches);
+    }
+
+    pub(crate) fn set_function(&mut self, function: Rc<FunctionDef<'a>>) {
+        Rc::make_mut(&mut self.functions).insert(function.name.clone(), function);
+    }
+
+    pub(crate) fn function(&self, name: &str) -> Res<Rc<FunctionDef<'a>>> {
+        self.functions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| inputerr(format!("undefined function {}()", name)))
+    }
+
+    // the variables and functions defined inside a block are not visible after it
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let (vars, functions) = (self.vars.clone(), self.functions.clone());
+        let result = f(self);
+        (self.vars, self.functions) = (vars, functions);
+        result
+    }
+
+    /// The context in which the body of a function runs: the caller's
+    /// context with the parameters bound to the arguments
+    fn call_context(&self, function: &FunctionDef<'a>, args: Vec<Xell>) -> Res<Self> {
+        if args.len() != function.params.len() {
+            return inputres(format!(
+                "function {}() takes {} arguments, got {}",
+                function.name,
+                function.params.len(),
+                args.len()
+            ));
+        }
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return inputres(format!(
+                "too many nested calls of {}(), the limit is {}",
+                function.name, MAX_CALL_DEPTH
+            ));
+        }
+        let mut ctx = self.clone();
+        ctx.call_depth += 1;
+        for (param, arg) in function.params.iter().zip(args) {
+            ctx.set_var(param.clone(), arg);
+        }
+        Ok(ctx)
+    }
+
+    /// Searches a path starting from the matches of a path function call
+    pub(crate) fn call_path(
+        &self,
+        name: &str,
+        args: Vec<Xell>,
+        path: &Path<'a>,
+    ) -> Res<Searcher<'a>> {
+        let function = self.function(name)?;
+        let FunctionBody::Path(body_start, body_path) = &function.body else {
+            return inputres(format!(
+                "{}() is a procedure, it can only be called as a statement",
+                name
+            ));
+        };
+        let ctx = self.call_context(&function, args)?;
+        let mut full_path = body_path.clone();
+        full_path.0.extend(path.0.iter().cloned());
+        Program::search(&ctx, body_start, &full_path)
+    }
 }
 
 impl<'a> Display for Program<'a> {
@@ -59,73 +231,393 @@ impl<'a> Display for Statement<'a> {
             Statement::Assignment(start, path, value) => {
                 write!(f, "{}{} = {}", start, path, value)?
             }
+            Statement::CompoundAssignment(start, path, op, value) => {
+                write!(f, "{}{} {}= {}", start, path, op, value)?
+            }
+            Statement::Create(start, path, value) => {
+                write!(f, "create {}{} = {}", start, path, value)?
+            }
             Statement::VarBind(name, start, path) => write!(f, "${} := {}{}", name, start, path)?,
+            Statement::Tree(start, path) => write!(f, "tree {}{}", start, path)?,
+            Statement::Delete(start, path) => write!(f, "delete {}{}", start, path)?,
+            Statement::Diff(left_start, left_path, right_start, right_path) => write!(
+                f,
+                "diff {}{} {}{}",
+                left_start, left_path, right_start, right_path
+            )?,
+            Statement::DiffBind(name, left_start, left_path, right_start, right_path) => write!(
+                f,
+                "${} := diff {}{} {}{}",
+                name, left_start, left_path, right_start, right_path
+            )?,
+            Statement::Copy(src_start, src_path, dst_start, dst_path) => write!(
+                f,
+                "copy {}{} {}{}",
+                src_start, src_path, dst_start, dst_path
+            )?,
+            Statement::FnDef(function) => {
+                write!(f, "fn {}(", function.name)?;
+                for (i, param) in function.params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "${}", param)?;
+                }
+                match &function.body {
+                    FunctionBody::Path(start, path) => write!(f, ") := {}{}", start, path)?,
+                    FunctionBody::Block(body) => {
+                        write!(f, ") ")?;
+                        write_block(f, body)?
+                    }
+                }
+            }
+            Statement::For(name, start, path, body) => {
+                write!(f, "for ${} in {}{} ", name, start, path)?;
+                write_block(f, body)?
+            }
+            Statement::If(start, path, then_body, else_body) => {
+                write!(f, "if {}{} ", start, path)?;
+                write_block(f, then_body)?;
+                match else_body.as_slice() {
+                    [] => {}
+                    [statement @ Statement::If(..)] => write!(f, " else {}", statement)?,
+                    _ => {
+                        write!(f, " else ")?;
+                        write_block(f, else_body)?
+                    }
+                }
+            }
         }
         Ok(())
     }
 }
 
+fn write_block(f: &mut Formatter<'_>, statements: &[Statement]) -> std::fmt::Result {
+    if statements.is_empty() {
+        return write!(f, "{{}}");
+    }
+    writeln!(f, "{{")?;
+    for statement in statements {
+        for line in statement.to_string().lines() {
+            writeln!(f, "    {}", line)?;
+        }
+    }
+    write!(f, "}}")
+}
+
 impl<'a> Program<'a> {
     pub fn parse(input: &str) -> Res<Program<'_>> {
-        let input = input.trim();
+        let input = input.trim_end();
         super::parse_program::parse_program(input)
     }
 
+    /// Blanks out the `#` comments of a program file, see `parse_program::blank_comments`.
+    pub fn strip_comments(text: &str) -> String {
+        super::parse_program::blank_comments(text)
+    }
+
     pub fn run(&self, params: ProgramParams) -> Res<()> {
         let mut ctx = ExecutionContext::default();
         self.run_in_context(params, &mut ctx)
     }
 
-    pub fn run_in_context(&self, params: ProgramParams, ctx: &mut ExecutionContext) -> Res<()> {
-        for statement in &self.0 {
+    /// Runs the program in a transaction: the modified files are written
+    /// together at the end, and none of them is written if anything fails.
+    /// Direct writes to the content of `^fs` cells are delayed as well, and
+    /// all writes are done in the order of the statements.
+    pub fn run_in_context(&self, params: ProgramParams, ctx: &mut ExecutionContext<'a>) -> Res<()> {
+        transaction::begin(params.dry_run)?;
+        if let Err(err) = Self::run_statements(&self.0, &params, ctx) {
+            transaction::abort();
+            return Err(err);
+        }
+        let changes = transaction::finish()?;
+        if !params.dry_run {
+            return transaction::commit(&changes);
+        }
+        for write in changes.writes {
+            let unchanged = write
+                .target
+                .read()
+                .value()
+                .is_ok_and(|v| v.as_cow_str() == write.serial.as_str());
+            if unchanged {
+                continue;
+            }
+            println!("would write {}:", write.file_path()?.display());
+            pprint_diff(&write.diff()?, params.color_palette);
+        }
+        for removal in changes.removals {
+            println!("would delete {}", removal.path.display());
+        }
+        Ok(())
+    }
+
+    fn run_statements(
+        statements: &[Statement<'a>],
+        params: &ProgramParams,
+        ctx: &mut ExecutionContext<'a>,
+    ) -> Res<()> {
+        for statement in statements {
             debug!("Running statement: {}", statement);
             match statement {
                 Statement::VarBind(name, start, path) => {
                     let value = Self::eval_to_single_cell(ctx, start, path.clone())?;
-                    ctx.vars.insert(name.clone(), value);
+                    ctx.set_var(name.clone(), value);
                 }
                 Statement::Assignment(start, path, value) => {
                     ifdebug!(println!("-- Assignment: {}{} = {}", start, path, value));
-                    let searcher = Searcher::new(Self::resolve_start(ctx, start)?, path.clone());
+                    let value = Self::eval_rvalue(ctx, value)?;
+                    let searcher = Self::search(ctx, start, path)?;
                     for cell in searcher {
-                        cell?.write().value(value.clone())?;
+                        rvalue::assign(&cell?, &value)?;
                     }
                 }
+                Statement::CompoundAssignment(start, path, op, value) => {
+                    ifdebug!(println!(
+                        "-- Compound assignment: {}{} {}= {}",
+                        start, path, op, value
+                    ));
+                    let value = Self::eval_rvalue(ctx, value)?;
+                    let searcher = Self::search(ctx, start, path)?;
+                    for cell in searcher {
+                        let cell = cell?;
+                        let current = match cell.read().value() {
+                            Ok(current) => current.to_owned_value(),
+                            Err(e) if e.kind == HErrKind::None => OwnValue::None,
+                            Err(e) => return Err(e),
+                        };
+                        let new_value = rvalue::apply(*op, &RValue::Value(current), &value)?;
+                        cell.write().value(new_value)?;
+                    }
+                }
+                Statement::Create(start, path, value) => {
+                    ifdebug!(println!("-- Create: {}{} = {}", start, path, value));
+                    Self::check_variables(ctx, path)?;
+                    let value = Self::eval_rvalue(ctx, value)?;
+                    let start = Self::resolve_start(ctx, start)?;
+                    create::assign_creating(ctx, start, path, &value)?;
+                }
+                Statement::Copy(src_start, src_path, dst_start, dst_path) => {
+                    ifdebug!(println!(
+                        "-- Copy: {}{} {}{}",
+                        src_start, src_path, dst_start, dst_path
+                    ));
+                    let mut sources = Self::search(ctx, src_start, src_path)?;
+                    let source = match sources.next() {
+                        Some(source) => source?,
+                        None => return noresm(format!("copy source {}{}", src_start, src_path)),
+                    };
+                    if sources.next().is_some() {
+                        return inputres(format!(
+                            "the copy source `{}{}` matches more than one cell, select one, e.g. with [0]",
+                            src_start, src_path
+                        ));
+                    }
+                    let destinations =
+                        Self::search(ctx, dst_start, dst_path)?.collect::<Res<Vec<_>>>()?;
+                    if destinations.is_empty() {
+                        return noresm(format!("copy destination {}{}", dst_start, dst_path));
+                    }
+                    for destination in &destinations {
+                        copy::copy_cell(&source, destination)?;
+                    }
+                }
+                Statement::Delete(start, path) => {
+                    ifdebug!(println!("-- Delete: {}{}", start, path));
+                    let cells = Self::search(ctx, start, path)?.collect::<Res<Vec<_>>>()?;
+                    // the matches are in document order; deleting them in reverse
+                    // order keeps the positions of the remaining matches valid
+                    for cell in cells.iter().rev() {
+                        cell.write().detach()?;
+                    }
+                }
+                Statement::Diff(left_start, left_path, right_start, right_path) => {
+                    let diff =
+                        Self::eval_diff(ctx, left_start, left_path, right_start, right_path)?;
+                    pprint_diff(&diff, params.color_palette);
+                }
+                Statement::DiffBind(name, left_start, left_path, right_start, right_path) => {
+                    let diff =
+                        Self::eval_diff(ctx, left_start, left_path, right_start, right_path)?;
+                    ctx.set_var(name.clone(), diff);
+                }
+                Statement::Tree(start, path) => {
+                    ifdebug!(println!("-- Tree: {} {}", start, path));
+                    let mut searcher = Self::search(ctx, start, path)?;
+                    let mut found = 0;
+                    let matches = std::iter::from_fn(|| searcher.next_with_captures())
+                        .take(params.match_limit.unwrap_or(usize::MAX))
+                        .inspect(|m| found += usize::from(m.is_ok()));
+                    if params.count {
+                        matches.collect::<Res<Vec<_>>>()?;
+                        println!("{}", found);
+                    } else {
+                        let tree = tree::result_tree(matches)?;
+                        output::print_match(&tree, params)?;
+                    }
+                    ctx.count_query(found);
+                }
+                Statement::Path(PathStart::Call(name, args), path) if path.0.is_empty() => {
+                    let function = ctx.function(name)?;
+                    if let FunctionBody::Block(body) = &function.body {
+                        ifdebug!(println!("-- Procedure call: {}", name));
+                        let args = Self::eval_args(ctx, args)?;
+                        let mut call_ctx = ctx.call_context(&function, args)?;
+                        Self::run_statements(body, params, &mut call_ctx)?;
+                        continue;
+                    }
+                    let cells = ctx.call_path(name, Self::eval_args(ctx, args)?, path)?;
+                    Self::print_matches(ctx, params, cells)?;
+                }
                 Statement::Path(start, path) => {
                     ifdebug!(println!("-- PathWithStart: {} {}", start, path));
-                    let searcher = Searcher::new(Self::resolve_start(ctx, start)?, path.clone());
-                    for cell in searcher {
-                        pprint(
-                            &cell?,
-                            params.print_depth,
-                            params.print_breadth,
-                            params.color_palette,
-                        );
+                    let searcher = Self::search(ctx, start, path)?;
+                    Self::print_matches(ctx, params, searcher)?;
+                }
+                Statement::FnDef(function) => ctx.set_function(function.clone()),
+                Statement::For(name, start, path, body) => {
+                    ifdebug!(println!("-- For: {} in {}{}", name, start, path));
+                    // the matches are collected first, so that the body can
+                    // change the cells it iterates over
+                    let cells = Self::search(ctx, start, path)?.collect::<Res<Vec<_>>>()?;
+                    for cell in cells {
+                        ctx.scoped(|ctx| {
+                            ctx.set_var(name.clone(), cell);
+                            Self::run_statements(body, params, ctx)
+                        })?;
                     }
                 }
+                Statement::If(start, path, then_body, else_body) => {
+                    ifdebug!(println!("-- If: {}{}", start, path));
+                    let first = Self::search(ctx, start, path)
+                        .and_then(|mut searcher| searcher.next().transpose());
+                    let found = match first {
+                        Ok(first) => first.is_some(),
+                        Err(e) if e.kind == HErrKind::None => false,
+                        Err(e) => return Err(e),
+                    };
+                    let body = if found { then_body } else { else_body };
+                    ctx.scoped(|ctx| Self::run_statements(body, params, ctx))?;
+                }
             }
         }
 
         Ok(())
     }
 
-    fn resolve_start(ctx: &ExecutionContext, start: &PathStart<'a>) -> Res<Xell> {
+    fn search(
+        ctx: &ExecutionContext<'a>,
+        start: &PathStart<'a>,
+        path: &Path<'a>,
+    ) -> Res<Searcher<'a>> {
+        Self::check_variables(ctx, path)?;
+        if let PathStart::Call(name, args) = start {
+            return ctx.call_path(name, Self::eval_args(ctx, args)?, path);
+        }
+        Ok(Searcher::new_in_context(
+            Self::resolve_start(ctx, start)?,
+            path.clone(),
+            ctx,
+        ))
+    }
+
+    fn check_variables(ctx: &ExecutionContext<'a>, path: &Path<'a>) -> Res<()> {
+        if let Some(name) = path
+            .variables()
+            .into_iter()
+            .find(|name| ctx.var(name).is_none())
+        {
+            return inputres(format!("undefined variable :{}", name));
+        }
+        Ok(())
+    }
+
+    fn resolve_start(ctx: &ExecutionContext<'a>, start: &PathStart<'a>) -> Res<Xell> {
         match start {
             PathStart::Var(name) => ctx
-                .vars
-                .get(name)
+                .var(name)
                 .cloned()
                 .ok_or_else(|| inputerr(format!("undefined variable :{}", name))),
+            PathStart::Call(..) => Self::eval_to_single_cell(ctx, start, Path(vec![])),
             _ => start.eval(),
         }
     }
 
+    /// Evaluates the arguments of a call, each to its first match
+    fn eval_args(ctx: &ExecutionContext<'a>, args: &[(PathStart<'a>, Path<'a>)]) -> Res<Vec<Xell>> {
+        args.iter()
+            .map(|(start, path)| Self::eval_to_single_cell(ctx, start, path.clone()))
+            .collect()
+    }
+
+    /// Evaluates the paths and the arithmetic of a right side into a value,
+    /// before anything is written; a path must match exactly one cell
+    fn eval_rvalue(ctx: &ExecutionContext<'a>, value: &RValue<'a>) -> Res<RValue<'a>> {
+        let (start, path) = match value {
+            RValue::Path(start, path) => (start, path),
+            RValue::Binary(left, op, right) => {
+                let left = Self::eval_rvalue(ctx, left)?;
+                let right = Self::eval_rvalue(ctx, right)?;
+                return Ok(RValue::Value(rvalue::apply(*op, &left, &right)?));
+            }
+            _ => return Ok(value.clone()),
+        };
+        let mut searcher = Self::search(ctx, start, path)?;
+        let cell = match searcher.next() {
+            Some(cell) => cell?,
+            None => return noresm(format!("{}{}", start, path)),
+        };
+        if searcher.next().is_some() {
+            return inputres(format!(
+                "the right side `{}{}` matches more than one cell, select one, e.g. with [0]",
+                start, path
+            ));
+        }
+        rvalue::from_cell(&cell)
+    }
+
+    /// Prints the matches, up to the match limit. The searcher is dropped
+    /// when the limit is reached, so the rest of the matches are never searched.
+    fn print_matches(
+        ctx: &ExecutionContext<'a>,
+        params: &ProgramParams,
+        cells: impl Iterator<Item = Res<Xell>>,
+    ) -> Res<()> {
+        let mut found = 0;
+        for cell in cells.take(params.match_limit.unwrap_or(usize::MAX)) {
+            let cell = cell?;
+            found += 1;
+            if !params.count {
+                output::print_match(&cell, params)?;
+            }
+        }
+        if params.count {
+            println!("{}", found);
+        }
+        ctx.count_query(found);
+        Ok(())
+    }
+
+    /// Diffs the first matches of two paths
+    fn eval_diff(
+        ctx: &ExecutionContext<'a>,
+        left_start: &PathStart<'a>,
+        left_path: &Path<'a>,
+        right_start: &PathStart<'a>,
+        right_path: &Path<'a>,
+    ) -> Res<Xell> {
+        let left = Self::eval_to_single_cell(ctx, left_start, left_path.clone())?;
+        let right = Self::eval_to_single_cell(ctx, right_start, right_path.clone())?;
+        left.diff(&right)
+    }
+
     fn eval_to_single_cell(
-        ctx: &ExecutionContext,
+        ctx: &ExecutionContext<'a>,
         start: &PathStart<'a>,
         path: Path<'a>,
     ) -> Res<Xell> {
-        let mut searcher = Searcher::new(Self::resolve_start(ctx, start)?, path.clone());
+        let mut searcher = Self::search(ctx, start, &path)?;
         let first = match searcher.next() {
             Some(Ok(cell)) => cell,
             Some(Err(err)) => return Err(err),
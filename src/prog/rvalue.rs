@@ -0,0 +1,308 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    api::*,
+    prog::{Path, PathStart},
+};
+
+/// The right side of an assignment: a scalar value, a json-like array or
+/// object literal, e.g. `["80:80", "443:443"]` or `{cpu: 2, mem: "1Gi"}`,
+/// or a path to another cell, e.g. `./staging.json^json/db`
+#[derive(Clone, Debug, PartialEq)]
+pub enum RValue<'a> {
+    Value(OwnValue),
+    Array(Vec<RValue<'a>>),
+    Object(Vec<(String, RValue<'a>)>),
+    // evaluated once per statement, must match a single cell
+    Path(PathStart<'a>, Path<'a>),
+    Binary(Box<RValue<'a>>, BinaryOp, Box<RValue<'a>>),
+}
+
+/// An arithmetic operator: `+` also concatenates strings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinaryOp {
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Sub => 1,
+            BinaryOp::Mul | BinaryOp::Div => 2,
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Sub => write!(f, "-"),
+            BinaryOp::Mul => write!(f, "*"),
+            BinaryOp::Div => write!(f, "/"),
+        }
+    }
+}
+
+impl From<OwnValue> for RValue<'_> {
+    fn from(value: OwnValue) -> Self {
+        RValue::Value(value)
+    }
+}
+
+impl Display for RValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RValue::Value(OwnValue::String(s)) => write_quoted(f, s),
+            RValue::Value(OwnValue::None) => write!(f, "null"),
+            RValue::Value(value) => write!(f, "{}", value),
+            RValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            RValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, item)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_quoted(f, key)?;
+                    write!(f, ": {}", item)?;
+                }
+                write!(f, "}}")
+            }
+            RValue::Path(start, path) => write!(f, "{}{}", start, path),
+            RValue::Binary(left, op, right) => {
+                // operators are left associative, so a right operand of the
+                // same precedence needs parentheses too
+                write_operand(f, left, op.precedence() > left.precedence())?;
+                write!(f, " {} ", op)?;
+                write_operand(f, right, op.precedence() >= right.precedence())
+            }
+        }
+    }
+}
+
+impl RValue<'_> {
+    fn precedence(&self) -> u8 {
+        match self {
+            RValue::Binary(_, op, _) => op.precedence(),
+            _ => u8::MAX,
+        }
+    }
+}
+
+fn write_operand(f: &mut Formatter<'_>, value: &RValue, parens: bool) -> std::fmt::Result {
+    if parens {
+        write!(f, "({})", value)
+    } else {
+        write!(f, "{}", value)
+    }
+}
+
+fn write_quoted(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes a value into a cell. A scalar is written as the cell value, an
+/// array or object replaces the cell with a new subtree.
+pub(crate) fn assign(cell: &Xell, value: &RValue) -> Res<()> {
+    let (ty, entries) = match value {
+        RValue::Value(value) => return cell.write().value(value.clone()),
+        RValue::Path(..) | RValue::Binary(..) => return fault("unevaluated value expression"),
+        RValue::Array(items) => ("array", items.iter().map(|v| (None, v)).collect::<Vec<_>>()),
+        RValue::Object(entries) => (
+            "object",
+            entries
+                .iter()
+                .map(|(k, v)| (Some(OwnValue::from(k.clone())), v))
+                .collect(),
+        ),
+    };
+    match cell.write().ty(ty) {
+        Err(e) if e.kind == HErrKind::None => {
+            return inputres(format!(
+                "cannot write {} `{}` into `{}`",
+                ty,
+                value,
+                cell.path().unwrap_or_default()
+            ));
+        }
+        res => res?,
+    }
+    let group = cell.sub().err()?;
+    for (label, item) in entries {
+        let leaf_value = match item {
+            RValue::Value(v) => Some(v.clone()),
+            _ => None,
+        };
+        let new_cell = match group.create(label, leaf_value) {
+            Err(e) if e.kind == HErrKind::None => {
+                return inputres(format!(
+                    "cannot create cells in `{}`",
+                    cell.path().unwrap_or_default()
+                ));
+            }
+            new_cell => new_cell?,
+        };
+        group.add(None, new_cell)?;
+        if !matches!(item, RValue::Value(_)) {
+            assign(&group.at(group.len()? - 1).err()?, item)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a cell into a value: a leaf cell into its value, a cell with sub
+/// cells into an object if they are labelled or into an array otherwise.
+/// Cells with attributes (e.g. xml elements) are refused, since the
+/// attributes cannot be represented in a value.
+pub(crate) fn from_cell(cell: &Xell) -> Res<RValue<'static>> {
+    match cell.attr().err() {
+        Ok(attrs) if !attrs.is_empty() => {
+            return inputres(format!(
+                "cannot read `{}` into a value, it has attributes",
+                cell.path()?
+            ));
+        }
+        Err(e) if e.kind != HErrKind::None => return Err(e),
+        _ => {}
+    }
+    let reader = cell.read().err()?;
+    let group = match cell.sub().err() {
+        Ok(group) => Some(group),
+        Err(e) if e.kind == HErrKind::None => None,
+        Err(e) => return Err(e),
+    };
+    let Some(group) = group.filter(|g| !g.is_empty()) else {
+        return Ok(match reader.ty()? {
+            "array" => RValue::Array(vec![]),
+            "object" | "table" => RValue::Object(vec![]),
+            _ => match reader.value() {
+                Ok(value) => RValue::Value(value.to_owned_value()),
+                Err(e) if e.kind == HErrKind::None => RValue::Value(OwnValue::None),
+                Err(e) => return Err(e),
+            },
+        });
+    };
+    let mut items = vec![];
+    for child in group {
+        let child = child.err()?;
+        let label = match child.read().label() {
+            Ok(label) => Some(label.as_cow_str().into_owned()),
+            Err(e) if e.kind == HErrKind::None => None,
+            Err(e) => return Err(e),
+        };
+        items.push((label, from_cell(&child)?));
+    }
+    if items.iter().all(|(label, _)| label.is_some()) {
+        Ok(RValue::Object(
+            items
+                .into_iter()
+                .map(|(label, item)| (label.unwrap_or_default(), item))
+                .collect(),
+        ))
+    } else {
+        Ok(RValue::Array(
+            items.into_iter().map(|(_, item)| item).collect(),
+        ))
+    }
+}
+
+/// Applies an arithmetic operator to two scalar values. Integers stay
+/// integers unless a division has a remainder, a float operand makes the
+/// result a float, and `+` concatenates two strings. Other combinations are
+/// type errors.
+pub(crate) fn apply(op: BinaryOp, left: &RValue, right: &RValue) -> Res<OwnValue> {
+    let mismatch = || {
+        inputres(format!(
+            "cannot apply `{}` to {} `{}` and {} `{}`",
+            op,
+            type_name(left),
+            left,
+            type_name(right),
+            right
+        ))
+    };
+    let (RValue::Value(l), RValue::Value(r)) = (left, right) else {
+        return mismatch();
+    };
+    match (l, r) {
+        (OwnValue::Int(a), OwnValue::Int(b)) => {
+            let (a, b) = (a.as_i128(), b.as_i128());
+            let n = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                BinaryOp::Div if b == 0 => {
+                    return inputres(format!("division by zero: `{}`", left));
+                }
+                BinaryOp::Div if a % b != 0 => {
+                    return Ok(OwnValue::Float(StrFloat(a as f64 / b as f64)));
+                }
+                BinaryOp::Div => a.checked_div(b),
+            };
+            let Some(n) = n else {
+                return overflow(op, left, right);
+            };
+            if let Ok(n) = i64::try_from(n) {
+                Ok(OwnValue::Int(Int::from(n)))
+            } else if let Ok(n) = u64::try_from(n) {
+                Ok(OwnValue::Int(Int::from(n)))
+            } else {
+                overflow(op, left, right)
+            }
+        }
+        (OwnValue::Int(_) | OwnValue::Float(_), OwnValue::Int(_) | OwnValue::Float(_)) => {
+            let (a, b) = (as_f64(l), as_f64(r));
+            let x = match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div if b == 0.0 => {
+                    return inputres(format!("division by zero: `{}`", left));
+                }
+                BinaryOp::Div => a / b,
+            };
+            Ok(OwnValue::Float(StrFloat(x)))
+        }
+        (OwnValue::String(a), OwnValue::String(b)) if op == BinaryOp::Add => {
+            Ok(OwnValue::String(format!("{}{}", a, b)))
+        }
+        _ => mismatch(),
+    }
+}
+
+fn overflow<T>(op: BinaryOp, left: &RValue, right: &RValue) -> Res<T> {
+    inputres(format!("integer overflow in `{} {} {}`", left, op, right))
+}
+
+fn as_f64(value: &OwnValue) -> f64 {
+    match value {
+        OwnValue::Int(i) => i.as_i128() as f64,
+        OwnValue::Float(f) => f.0,
+        _ => f64::NAN,
+    }
+}
+
+fn type_name(value: &RValue) -> &'static str {
+    match value {
+        RValue::Value(OwnValue::None) => "null",
+        RValue::Value(OwnValue::Bool(_)) => "bool",
+        RValue::Value(OwnValue::Int(_)) => "int",
+        RValue::Value(OwnValue::Float(_)) => "float",
+        RValue::Value(OwnValue::String(_)) => "string",
+        RValue::Array(_) => "array",
+        RValue::Object(_) => "object",
+        RValue::Path(..) | RValue::Binary(..) => "expression",
+    }
+}
@@ -5,12 +5,14 @@
 /// kleene) can lead to a split of the search path locations that match the
 /// cell.
 /// Loosely inspired from https://swtch.com/~rsc/regexp/regexp2.html
+use std::cmp::Ordering;
+
 use crate::{
     api::*,
     debug_err, guard_ok, guard_some,
     prog::{
-        Path,
-        path::{Expression, PathItem},
+        ExecutionContext, Path,
+        path::{Expression, Filter, Function, Index, Operand, ParentPathItem, PathItem},
     },
     warning,
 };
@@ -23,14 +25,16 @@ macro_rules! ifdebug {
     };
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Searcher<'s> {
     path: Vec<PathItem<'s>>,
     // dfs exploration of the cell tree in search of the path
-    stack: Vec<MatchTest>,
+    stack: Vec<StackItem>,
     // to find out where the search failed
     next_max_path_index: usize,
     filter_eval: bool,
+    // variables which filters can refer to
+    ctx: ExecutionContext<'s>,
 }
 
 /// a cell to be matched against path_index
@@ -40,8 +44,50 @@ pub struct MatchTest {
     parent: Xell,
     // path index to be tested
     path_index: usize,
+    // cells bound by `[as name]` on the way to this match
+    captures: Captures,
 }
 
+/// Cells selected by label, tested against path_index one at a time when
+/// they are popped, so that a search which stops early does not read them all
+struct Candidates {
+    cells: Box<dyn Iterator<Item = Xell>>,
+    path_index: usize,
+    captures: Captures,
+}
+
+impl std::fmt::Debug for Candidates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Candidates")
+            .field("path_index", &self.path_index)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+enum StackItem {
+    Match(MatchTest),
+    Candidates(Candidates),
+}
+
+impl StackItem {
+    fn path_index(&self) -> usize {
+        match self {
+            StackItem::Match(mt) => mt.path_index,
+            StackItem::Candidates(c) => c.path_index,
+        }
+    }
+}
+
+impl From<MatchTest> for StackItem {
+    fn from(mt: MatchTest) -> Self {
+        StackItem::Match(mt)
+    }
+}
+
+/// Cells bound by name with `[as name]` in a path, in path order
+pub type Captures = Vec<(String, Xell)>;
+
 //  Let us have this tree:
 //  root:
 //      a:
@@ -70,10 +116,24 @@ pub struct MatchTest {
 
 impl<'s> Searcher<'s> {
     pub(crate) fn new(start: Xell, path: Path<'s>) -> Searcher<'s> {
-        Self::new_with_filter_eval(start, path, false)
+        Self::new_with_filter_eval(start, path, false, ExecutionContext::default())
+    }
+
+    /// Creates a searcher whose filters can use the variables of the context
+    pub(crate) fn new_in_context(
+        start: Xell,
+        path: Path<'s>,
+        ctx: &ExecutionContext<'s>,
+    ) -> Searcher<'s> {
+        Self::new_with_filter_eval(start, path, false, ctx.for_search())
     }
 
-    fn new_with_filter_eval(start: Xell, path: Path<'s>, filter_eval: bool) -> Searcher<'s> {
+    fn new_with_filter_eval(
+        start: Xell,
+        path: Path<'s>,
+        filter_eval: bool,
+        ctx: ExecutionContext<'s>,
+    ) -> Searcher<'s> {
         ifdebug!(println!(
             "\nnew Searcher, path: {:?}:",
             path.0
@@ -85,25 +145,30 @@ impl<'s> Searcher<'s> {
         let start_match = MatchTest {
             parent: start,
             path_index: 0,
+            captures: vec![],
         };
         Searcher {
             path: path.0,
-            stack: vec![start_match],
+            stack: vec![start_match.into()],
             next_max_path_index: 0,
             filter_eval,
+            ctx,
         }
     }
 
-    fn eval_next(&mut self) -> Option<Res<Xell>> {
+    /// Returns the next match together with the cells captured by
+    /// `[as name]` path items on the way to it
+    pub fn next_with_captures(&mut self) -> Option<Res<(Xell, Captures)>> {
         while !self.stack.is_empty() {
-            if let Some(cell) = self.pump_stack() {
-                Self::update_next_max_path_index(&self.stack, &mut self.next_max_path_index);
+            let found = self.pump_stack();
+            Self::update_next_max_path_index(&self.stack, &mut self.next_max_path_index);
+            if let Some(found) = found {
                 ifdebug!(println!(
                     "returning cell {:?}",
-                    cell.as_ref().map(|x| x.debug_string())
+                    found.as_ref().map(|x| x.0.debug_string())
                 ));
-                match cell.and_then(|cell| cell.err()) {
-                    Ok(cell) => return Some(Ok(cell)),
+                match found.and_then(|(cell, captures)| Ok((cell.err()?, captures))) {
+                    Ok(found) => return Some(Ok(found)),
                     Err(e) => {
                         if e.kind != HErrKind::None {
                             warning!("search error: {}", e)
@@ -115,7 +180,7 @@ impl<'s> Searcher<'s> {
         None
     }
 
-    fn pump_stack(&mut self) -> Option<Res<Xell>> {
+    fn pump_stack(&mut self) -> Option<Res<(Xell, Captures)>> {
         ifdebug!(println!(
             "----\nstack:{}",
             self.stack
@@ -126,10 +191,32 @@ impl<'s> Searcher<'s> {
         ));
 
         // pop the last cell match from the stack
-        let MatchTest { parent, path_index } = guard_some!(self.stack.pop(), { return None });
+        let MatchTest {
+            parent,
+            path_index,
+            captures,
+        } = match guard_some!(self.stack.pop(), { return None }) {
+            StackItem::Match(mt) => mt,
+            StackItem::Candidates(mut candidates) => {
+                let cell = guard_some!(candidates.cells.next(), { return None });
+                let (path_index, captures) = (candidates.path_index, candidates.captures.clone());
+                // the rest of the candidates are tested after the subtree of this one
+                self.stack.push(StackItem::Candidates(candidates));
+                Self::process_cell(
+                    &mut self.stack,
+                    &self.ctx,
+                    &self.path,
+                    cell,
+                    &captures,
+                    path_index,
+                    true,
+                );
+                return None;
+            }
+        };
 
         if path_index >= self.path.len() {
-            return Some(Ok(parent));
+            return Some(Ok((parent, captures)));
         }
         let pi = &self.path[path_index];
 
@@ -142,19 +229,40 @@ impl<'s> Searcher<'s> {
 
         match pi {
             PathItem::Elevation(npi) => {
+                // After `*` or `**`, some candidates may not expose an elevation child; skip them.
+                let skip_missing = self.filter_eval
+                    || (path_index > 0
+                        && matches!(
+                            &self.path[path_index - 1],
+                            PathItem::Normal(NormalPathItem {
+                                selector: Some(
+                                    Selector::Star | Selector::DoubleStar | Selector::TripleStar
+                                ),
+                                ..
+                            })
+                        ));
                 let opt_res = Self::process_elevation(
                     &mut self.stack,
-                    &self.path,
-                    self.filter_eval,
+                    skip_missing,
                     npi,
                     parent,
+                    &captures,
                     path_index,
-                    &mut self.next_max_path_index,
                 );
                 if let Some(Err(e)) = opt_res {
                     return Some(Err(e));
                 }
             }
+            PathItem::Parent(ppi) => {
+                Self::process_parent(
+                    &mut self.stack,
+                    &self.ctx,
+                    ppi,
+                    parent,
+                    &captures,
+                    path_index,
+                );
+            }
             PathItem::Normal(npi) => {
                 let group = match npi.relation {
                     Relation::Sub => parent.sub(),
@@ -173,24 +281,29 @@ impl<'s> Searcher<'s> {
                     Ok(group) => {
                         Self::process_group(
                             &mut self.stack,
+                            &self.ctx,
                             &self.path,
                             &parent,
                             group,
+                            &captures,
                             path_index,
-                            &mut self.next_max_path_index,
                         );
                     }
                 }
 
-                // test for doublestar matching nothing, i.e. the parent against the next path item
-                if npi.selector == Some(Selector::DoubleStar) {
+                // test for doublestar matching nothing, i.e. the parent against the next path item;
+                // `**[filter]` works as `**/*[filter]` so the parent is not tested against the filter
+                if (npi.selector == Some(Selector::DoubleStar) && npi.filters.is_empty())
+                    || npi.selector == Some(Selector::TripleStar)
+                {
                     Self::process_cell(
                         &mut self.stack,
+                        &self.ctx,
                         &self.path,
                         parent,
+                        &captures,
                         path_index,
                         true,
-                        &mut self.next_max_path_index,
                     )
                 }
             }
@@ -198,15 +311,52 @@ impl<'s> Searcher<'s> {
         None
     }
 
+    fn process_parent(
+        stack: &mut Vec<StackItem>,
+        ctx: &ExecutionContext<'s>,
+        ppi: &ParentPathItem,
+        cell: Xell,
+        captures: &Captures,
+        path_index: usize,
+    ) {
+        let ancestors = if ppi.ancestors {
+            cell.ancestors()
+        } else {
+            cell.parent().map(|p| vec![p])
+        };
+        let ancestors = guard_ok!(ancestors, err => {
+            if err.kind != HErrKind::None {
+                warning!("Error while searching: cannot get parent: {:?}", err);
+            }
+            return;
+        });
+        // push in reverse, so that the nearest ancestor is popped first
+        for ancestor in ancestors.into_iter().rev() {
+            if Self::eval_filters_match(ctx, &ancestor, &ppi.filters) {
+                ifdebug!(println!(
+                    "match, push (parent): `{}`",
+                    ancestor.debug_string()
+                ));
+                stack.push(
+                    MatchTest {
+                        parent: ancestor,
+                        path_index: path_index + 1,
+                        captures: captures.clone(),
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+
     #[must_use]
     fn process_elevation(
-        stack: &mut Vec<MatchTest>,
-        path: &[PathItem],
-        filter_eval: bool,
+        stack: &mut Vec<StackItem>,
+        skip_missing: bool,
         epi: &ElevationPathItem,
         parent: Xell,
+        captures: &Captures,
         path_index: usize,
-        next_max_path_index: &mut usize,
     ) -> Option<Res<()>> {
         ifdebug!(println!(
             "process_elevation, parent: {}, epi: {:?}",
@@ -260,15 +410,7 @@ impl<'s> Searcher<'s> {
             }
         }
         let cell = guard_ok!(itp_cell.sub().at(0).err(), err => {
-            // After `*` or `**`, some candidates may not expose an elevation child; skip them.
-            if filter_eval|| (path_index > 0 && matches!(
-                    &path[path_index - 1],
-                    PathItem::Normal(NormalPathItem {
-                        selector: Some(Selector::Star | Selector::DoubleStar),
-                        ..
-                    })
-                ))
-            {
+            if skip_missing {
                 return None;
             }
 
@@ -280,45 +422,77 @@ impl<'s> Searcher<'s> {
             cell.debug_string(),
             path_index + 1
         ));
-        stack.push(MatchTest {
-            parent: cell.clone(),
-            path_index: path_index + 1,
-        });
-        Self::update_next_max_path_index(stack, next_max_path_index);
+        stack.push(
+            MatchTest {
+                parent: cell.clone(),
+                path_index: path_index + 1,
+                captures: captures.clone(),
+            }
+            .into(),
+        );
         None
     }
 
     fn process_group(
-        stack: &mut Vec<MatchTest>,
+        stack: &mut Vec<StackItem>,
+        ctx: &ExecutionContext<'s>,
         path: &[PathItem],
         parent: &Xell,
         group: Group,
+        captures: &Captures,
         path_index: usize,
-        next_max_path_index: &mut usize,
     ) {
         let pi = match &path[path_index] {
-            PathItem::Elevation(_) => panic!("elevation path item unexpected here"),
             PathItem::Normal(npi) => npi,
+            _ => panic!("normal path item expected here"),
         };
         match (pi.selector, pi.index) {
-            (Some(Selector::Star) | Some(Selector::DoubleStar), None) => {
+            (Some(Selector::Star | Selector::DoubleStar | Selector::TripleStar), None)
+            | (
+                None | Some(Selector::Star | Selector::DoubleStar | Selector::TripleStar),
+                Some(Index::Range(..)),
+            ) => {
                 ifdebug!(println!("iterating over all children"));
-                for i in (0..group.len().unwrap_or(0)).rev() {
+                let len = group.len().unwrap_or(0);
+                let range = match pi.index {
+                    Some(Index::Range(start, end)) => Index::range(start, end, len),
+                    _ => 0..len,
+                };
+                // `**[filter]` works as `**/*[filter]`
+                let leaf_filter =
+                    pi.selector == Some(Selector::DoubleStar) && !pi.filters.is_empty();
+                for i in range.rev() {
                     let cell = guard_ok!(group.at(i).err(), err => {
                         warning!("Error while searching: cannot get cell: {:?}", err);
                         continue;
                     });
+                    if leaf_filter {
+                        // descend regardless of the filter, which is only
+                        // tested on the cells matched as the end of `**`
+                        stack.push(
+                            MatchTest {
+                                parent: cell.clone(),
+                                path_index,
+                                captures: captures.clone(),
+                            }
+                            .into(),
+                        );
+                    }
                     Self::process_cell(
                         stack,
+                        ctx,
                         path,
                         cell,
+                        captures,
                         path_index,
-                        pi.selector != Some(Selector::DoubleStar),
-                        next_max_path_index,
+                        !Self::is_kleene(pi) || leaf_filter,
                     )
                 }
             }
-            (None | Some(Selector::Star) | Some(Selector::DoubleStar), Some(index)) => {
+            (
+                None | Some(Selector::Star | Selector::DoubleStar | Selector::TripleStar),
+                Some(Index::At(index)),
+            ) => {
                 ifdebug!(println!("get child by index"));
                 let at_index = if index < 0 {
                     let len = group.len().unwrap_or_else(|e| {
@@ -337,35 +511,72 @@ impl<'s> Searcher<'s> {
                 });
                 Self::process_cell(
                     stack,
+                    ctx,
                     path,
                     cell,
+                    captures,
                     path_index,
-                    pi.selector != Some(Selector::DoubleStar),
-                    next_max_path_index,
+                    !Self::is_kleene(pi),
                 );
             }
             (Some(Selector::Str(label)), opt_index) => {
                 ifdebug!(println!("iterating over children by label"));
-                let mut iter = guard_ok!(group.get_all(label).err(), err => {
+                let iter = guard_ok!(group.get_all(label).err(), err => {
                     if err.kind != HErrKind::None {
                         warning!("Error while searching: cannot get cell iterator: {:?}", err);
                     }
                     return ;
                 });
-                if let Some(index) = opt_index {
-                    let opt_cell = if index < 0 {
-                        iter.rev().nth((-index - 1) as usize)
-                    } else {
-                        iter.nth(index as usize)
-                    };
-                    if let Some(cell) = opt_cell {
-                        Self::process_cell(stack, path, cell, path_index, true, next_max_path_index)
+                Self::process_labelled_cells(
+                    stack, ctx, path, iter, captures, opt_index, path_index,
+                );
+            }
+            (Some(selector @ Selector::Alt(_)), opt_index) => {
+                ifdebug!(println!("iterating over children by alternative labels"));
+                let mut labels: Vec<&str> = vec![];
+                for label in selector.alternatives() {
+                    if !labels.contains(&label) {
+                        labels.push(label);
                     }
-                } else {
-                    for cell in iter {
-                        Self::process_cell(stack, path, cell, path_index, true, next_max_path_index)
+                }
+                let mut cells = vec![];
+                for label in labels {
+                    if is_glob(label) {
+                        cells.extend(Self::glob_cells(&group, label));
+                        continue;
                     }
+                    let iter = guard_ok!(group.get_all(label).err(), err => {
+                        if err.kind != HErrKind::None {
+                            warning!("Error while searching: cannot get cell iterator: {:?}", err);
+                        }
+                        continue;
+                    });
+                    cells.extend(iter);
                 }
+                let mut cells = cells
+                    .into_iter()
+                    .map(|cell| (cell.read().index().ok(), cell))
+                    .collect::<Vec<_>>();
+                cells.sort_by_key(|(index, _)| index.unwrap_or(usize::MAX));
+                // several patterns can match the same cell
+                cells.dedup_by(|(a, _), (b, _)| a.is_some() && a == b);
+                let cells = cells.into_iter().map(|(_, cell)| cell);
+                Self::process_labelled_cells(
+                    stack, ctx, path, cells, captures, opt_index, path_index,
+                );
+            }
+            (Some(Selector::Glob(pattern)), opt_index) => {
+                ifdebug!(println!("iterating over children by label pattern"));
+                let cells = Self::glob_cells(&group, pattern);
+                Self::process_labelled_cells(
+                    stack,
+                    ctx,
+                    path,
+                    cells.into_iter(),
+                    captures,
+                    opt_index,
+                    path_index,
+                );
             }
             (None, None) => {
                 warning!("missing both selector and index in search");
@@ -373,13 +584,90 @@ impl<'s> Searcher<'s> {
         }
     }
 
+    // `**` and `***` match cells at any depth, so matching a cell does not advance the path
+    fn is_kleene(pi: &NormalPathItem) -> bool {
+        matches!(
+            pi.selector,
+            Some(Selector::DoubleStar | Selector::TripleStar)
+        )
+    }
+
+    /// Returns the cells of the group with labels matching a glob pattern.
+    /// Only the cells which can start with the literal prefix of the pattern
+    /// are tested if the group supports it.
+    fn glob_cells(group: &Group, pattern: &str) -> Vec<Xell> {
+        let len = group.len().unwrap_or(0);
+        let prefix = glob_prefix(pattern);
+        let range = if prefix.is_empty() {
+            0..len
+        } else {
+            group.label_prefix_range(prefix).unwrap_or(0..len)
+        };
+        let mut cells = vec![];
+        for i in range {
+            let cell = guard_ok!(group.at(i).err(), err => {
+                warning!("Error while searching: cannot get cell: {:?}", err);
+                continue;
+            });
+            let matches = match cell.read().label() {
+                Ok(Value::Str(label)) => glob_match(pattern, label),
+                _ => false,
+            };
+            if matches {
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+
+    fn process_labelled_cells(
+        stack: &mut Vec<StackItem>,
+        ctx: &ExecutionContext<'s>,
+        path: &[PathItem],
+        mut cells: impl DoubleEndedIterator<Item = Xell> + 'static,
+        captures: &Captures,
+        index: Option<Index>,
+        path_index: usize,
+    ) {
+        let cells: Box<dyn Iterator<Item = Xell>> = match index {
+            Some(Index::At(index)) => {
+                let opt_cell = if index < 0 {
+                    cells.rev().nth((-index - 1) as usize)
+                } else {
+                    cells.nth(index as usize)
+                };
+                if let Some(cell) = opt_cell {
+                    Self::process_cell(stack, ctx, path, cell, captures, path_index, true)
+                }
+                return;
+            }
+            Some(Index::Range(start, end)) if start.unwrap_or(0) < 0 || end.unwrap_or(0) < 0 => {
+                // negative bounds need the number of matching cells
+                let cells = cells.collect::<Vec<_>>();
+                let range = Index::range(start, end, cells.len());
+                Box::new(cells.into_iter().skip(range.start).take(range.len()))
+            }
+            Some(Index::Range(start, end)) => {
+                let range = Index::range(start, end, usize::MAX);
+                Box::new(cells.skip(range.start).take(range.len()))
+            }
+            None => Box::new(cells),
+        };
+        stack.push(StackItem::Candidates(Candidates {
+            cells,
+            path_index,
+            captures: captures.clone(),
+        }));
+    }
+
     fn process_cell(
-        stack: &mut Vec<MatchTest>,
+        stack: &mut Vec<StackItem>,
+        ctx: &ExecutionContext<'s>,
         path: &[PathItem],
         cell: Xell,
+        captures: &Captures,
         path_index: usize,
         advance_index: bool,
-        next_max_path_index: &mut usize,
     ) {
         let cell = guard_ok!(cell.err(), err => {
             if err.kind != HErrKind::None {
@@ -389,13 +677,13 @@ impl<'s> Searcher<'s> {
         });
 
         let pi = match &path[path_index] {
-            PathItem::Elevation(_) => panic!("elevation path item not expected here"),
             PathItem::Normal(npi) => npi,
+            _ => panic!("normal path item expected here"),
         };
 
         ifdebug!(println!("test: `{}` for {}", cell.debug_string(), pi));
 
-        if !Self::eval_filters_match(&cell, pi) {
+        if !Self::eval_filters_match(ctx, &cell, &pi.filters) {
             ifdebug!(println!("no match `{}` for {}", cell.debug_string(), pi));
             return;
         }
@@ -411,16 +699,23 @@ impl<'s> Searcher<'s> {
             cell.debug_string(),
             next_path_index
         ));
-        stack.push(MatchTest {
-            parent: cell.clone(),
-            path_index: next_path_index,
-        });
-        Self::update_next_max_path_index(stack, next_max_path_index);
+        let mut captures = captures.clone();
+        if let (true, Some(name)) = (advance_index, pi.capture) {
+            captures.push((name.to_string(), cell.clone()));
+        }
+        stack.push(
+            MatchTest {
+                parent: cell,
+                path_index: next_path_index,
+                captures,
+            }
+            .into(),
+        );
     }
 
-    fn eval_filters_match(subcell: &Xell, path_item: &NormalPathItem) -> bool {
-        for filter in &path_item.filters {
-            match Searcher::eval_expression(subcell.clone(), &filter.expr) {
+    fn eval_filters_match(ctx: &ExecutionContext<'s>, subcell: &Xell, filters: &[Filter]) -> bool {
+        for filter in filters {
+            match Searcher::eval_expression(ctx, subcell.clone(), &filter.expr) {
                 Err(e) => {
                     ifdebug!(println!("eval_bool_expression failed"));
                     debug_err!(e);
@@ -440,27 +735,47 @@ impl<'s> Searcher<'s> {
         true
     }
 
-    fn eval_expression(cell: Xell, expr: &Expression<'s>) -> Res<bool> {
+    fn eval_expression(ctx: &ExecutionContext<'s>, cell: Xell, expr: &Expression<'s>) -> Res<bool> {
         match expr {
             Expression::Ternary { left, op_right } => {
-                Self::eval_ternary_expression(cell, left.clone(), op_right)
+                Self::eval_ternary_expression(ctx, cell, left.clone(), op_right)
             }
             Expression::Type { ty } => cell.read().ty().map(|t| t == *ty),
+            Expression::Call {
+                function,
+                arg,
+                op_right,
+            } => Self::eval_call_expression(ctx, cell, *function, arg.clone(), op_right),
+            Expression::UserCall {
+                name,
+                args,
+                op_right,
+            } => Self::eval_user_call_expression(ctx, cell, name, args, op_right),
             Expression::Or { expressions } => {
                 for expr in expressions {
-                    if Self::eval_expression(cell.clone(), expr)? {
+                    if Self::eval_expression(ctx, cell.clone(), expr)? {
                         return Ok(true);
                     }
                 }
                 Ok(false)
             }
+            Expression::And { expressions } => {
+                for expr in expressions {
+                    if !Self::eval_expression(ctx, cell.clone(), expr)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Expression::Not { expression } => Ok(!Self::eval_expression(ctx, cell, expression)?),
         }
     }
 
     fn eval_ternary_expression(
+        ctx: &ExecutionContext<'s>,
         cell: Xell,
         left: Path<'s>,
-        op_right: &Option<(&'s str, OwnValue)>,
+        op_right: &Option<(&'s str, Operand<'s>)>,
     ) -> Res<bool> {
         ifdebug!(println!(
             "{{{{\neval_ternary_expression cell `{}` for expr `{}`",
@@ -468,24 +783,26 @@ impl<'s> Searcher<'s> {
             expr
         ));
 
-        fn eval_expr(op: &str, left: Value, right: &OwnValue) -> Res<bool> {
-            if !["==", "!="].contains(&op) {
-                return inputres(format!("bad operand: {}", op));
-            }
-            match op {
-                "==" if left == right.as_value() => Ok(true),
-                "!=" if left != right.as_value() => Ok(true),
-                _ => Ok(false),
-            }
-        }
+        let op_right = match op_right {
+            Some((op, right)) => Some((*op, Self::eval_operand(ctx, &cell, right)?)),
+            None => None,
+        };
+        let eval_iter_left = Self::new_with_filter_eval(cell, left, true, ctx.clone());
+        Self::eval_any_match(eval_iter_left, &op_right)
+    }
 
-        let eval_iter_left = Self::new_with_filter_eval(cell, left, true);
-        for cell in eval_iter_left {
+    /// True if any of the cells exists, or has a value satisfying the
+    /// comparison if there is one
+    fn eval_any_match(
+        cells: impl Iterator<Item = Res<Xell>>,
+        op_right: &Option<(&str, Vec<Operand<'s>>)>,
+    ) -> Res<bool> {
+        for cell in cells {
             let cell = guard_ok!(cell, err => {
                 debug_err!(err);
                 continue;
             });
-            if let Some((op, right)) = op_right {
+            if let Some((op, right)) = &op_right {
                 let reader = guard_ok!(cell.read().err(), err => {
                     debug_err!(err);
                     continue;
@@ -495,7 +812,7 @@ impl<'s> Searcher<'s> {
                     debug_err!(err);
                     continue;
                 });
-                if eval_expr(op, lvalue, right)? {
+                if eval_op_any(op, lvalue, right)? {
                     ifdebug!(println!("eval_bool_expression true\n}}}}"));
                     return Ok(true);
                 }
@@ -508,8 +825,134 @@ impl<'s> Searcher<'s> {
         Ok(false)
     }
 
-    fn update_next_max_path_index(stack: &[MatchTest], next_max_path_index: &mut usize) {
-        let max_i = stack.iter().map(|cn| cn.path_index).max().unwrap_or(0);
+    fn eval_user_call_expression(
+        ctx: &ExecutionContext<'s>,
+        cell: Xell,
+        name: &str,
+        args: &[Operand<'s>],
+        op_right: &Option<(&'s str, Operand<'s>)>,
+    ) -> Res<bool> {
+        let op_right = match op_right {
+            Some((op, right)) => Some((*op, Self::eval_operand(ctx, &cell, right)?)),
+            None => None,
+        };
+        let mut arg_cells = vec![];
+        for arg in args {
+            let (start, path) = match arg {
+                Operand::Path(path) => (cell.clone(), path),
+                Operand::Var(name, path) => (
+                    ctx.var(name)
+                        .cloned()
+                        .ok_or_else(|| inputerr(format!("undefined variable :{}", name)))?,
+                    path,
+                ),
+                _ => return fault("bad function argument"),
+            };
+            let first = Self::new_with_filter_eval(start, path.clone(), true, ctx.clone()).next();
+            match first {
+                Some(arg_cell) => arg_cells.push(arg_cell?),
+                None => return Ok(false),
+            }
+        }
+        let matches = ctx.call_path(name, arg_cells, &Path(vec![]))?;
+        Self::eval_any_match(matches, &op_right)
+    }
+
+    fn eval_call_expression(
+        ctx: &ExecutionContext<'s>,
+        cell: Xell,
+        function: Function,
+        arg: Path<'s>,
+        op_right: &Option<(&'s str, Operand<'s>)>,
+    ) -> Res<bool> {
+        let op_right = match op_right {
+            Some((op, right)) => Some((*op, Self::eval_operand(ctx, &cell, right)?)),
+            None => None,
+        };
+        let mut values = vec![];
+        for cell in Self::new_with_filter_eval(cell, arg, true, ctx.clone()) {
+            let cell = guard_ok!(cell, err => {
+                debug_err!(err);
+                continue;
+            });
+            if function == Function::Count {
+                values.push(OwnValue::None);
+                continue;
+            }
+            let reader = guard_ok!(cell.read().err(), err => {
+                debug_err!(err);
+                continue;
+            });
+            let value = guard_ok!(reader.value(), err => {
+                debug_err!(err);
+                continue;
+            });
+            values.push(value.to_owned_value());
+            if function == Function::Len {
+                break;
+            }
+        }
+
+        let result = aggregate(function, &values);
+        ifdebug!(println!("eval_call_expression {} = {:?}", function, result));
+        match op_right {
+            Some((op, right)) => eval_op_any(op, result.as_value(), &right),
+            None => Ok(match result {
+                OwnValue::None | OwnValue::Bool(false) => false,
+                OwnValue::Int(i) => i.as_i128() != 0,
+                OwnValue::Float(f) => f.0 != 0.0,
+                OwnValue::String(s) => !s.is_empty(),
+                OwnValue::Bool(true) => true,
+            }),
+        }
+    }
+
+    /// Evaluates the right side of a filter comparison for a cell. Literals
+    /// and regexes are used as they are, while a relative path (starting from
+    /// the cell) or a variable path stands for the values of all the cells it
+    /// matches. A variable path does not depend on the cell, so its values
+    /// are evaluated once per search.
+    fn eval_operand(
+        ctx: &ExecutionContext<'s>,
+        cell: &Xell,
+        operand: &Operand<'s>,
+    ) -> Res<Vec<Operand<'s>>> {
+        let values = match operand {
+            Operand::Value(_) | Operand::Regex(_) => return Ok(vec![operand.clone()]),
+            Operand::Path(path) => Self::path_values(ctx, cell.clone(), path)?,
+            Operand::Var(name, path) => ctx.operand_values(&operand.to_string(), || {
+                let start = ctx
+                    .var(name)
+                    .cloned()
+                    .ok_or_else(|| inputerr(format!("undefined variable :{}", name)))?;
+                Self::path_values(ctx, start, path)
+            })?,
+        };
+        Ok(values.into_iter().map(Operand::Value).collect())
+    }
+
+    fn path_values(ctx: &ExecutionContext<'s>, start: Xell, path: &Path<'s>) -> Res<Vec<OwnValue>> {
+        let mut values = vec![];
+        for cell in Self::new_with_filter_eval(start, path.clone(), true, ctx.clone()) {
+            let cell = guard_ok!(cell, err => {
+                debug_err!(err);
+                continue;
+            });
+            let reader = guard_ok!(cell.read().err(), err => {
+                debug_err!(err);
+                continue;
+            });
+            let value = guard_ok!(reader.value(), err => {
+                debug_err!(err);
+                continue;
+            });
+            values.push(value.to_owned_value());
+        }
+        Ok(values)
+    }
+
+    fn update_next_max_path_index(stack: &[StackItem], next_max_path_index: &mut usize) {
+        let max_i = stack.iter().map(|cn| cn.path_index()).max().unwrap_or(0);
         if max_i > *next_max_path_index {
             *next_max_path_index = max_i;
         }
@@ -528,9 +971,174 @@ impl<'s> Searcher<'s> {
     }
 }
 
+/// Applies a filter comparison operator to a left side value and each of
+/// the evaluated right side operands, true if any comparison is true.
+fn eval_op_any(op: &str, left: Value, rights: &[Operand]) -> Res<bool> {
+    for right in rights {
+        if eval_op(op, left, right)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Applies a filter comparison operator to a left side value.
+fn eval_op(op: &str, left: Value, right: &Operand) -> Res<bool> {
+    let right = match right {
+        Operand::Value(v) => v.as_value(),
+        Operand::Regex(regex) => {
+            return Ok(match left {
+                Value::None | Value::Bytes => false,
+                _ => regex.0.is_match(left.as_cow_str().as_ref()),
+            });
+        }
+        Operand::Path(_) | Operand::Var(..) => return fault("unevaluated path operand"),
+    };
+    match op {
+        "==" => Ok(values_equal(left, right)),
+        "!=" => Ok(!values_equal(left, right)),
+        "<" | "<=" | ">" | ">=" => {
+            let ord = guard_some!(compare_values(left, right), { return Ok(false) });
+            Ok(match op {
+                "<" => ord.is_lt(),
+                "<=" => ord.is_le(),
+                ">" => ord.is_gt(),
+                _ => ord.is_ge(),
+            })
+        }
+        "startswith" | "endswith" | "contains" => {
+            if matches!(left, Value::None | Value::Bytes) {
+                return Ok(false);
+            }
+            let (left, right) = (left.as_cow_str(), right.as_cow_str());
+            Ok(match op {
+                "startswith" => left.starts_with(right.as_ref()),
+                "endswith" => left.ends_with(right.as_ref()),
+                _ => left.contains(right.as_ref()),
+            })
+        }
+        _ => inputres(format!("bad operand: {}", op)),
+    }
+}
+
+/// Tests two values for the `==` and `!=` filter operators, using the same
+/// coercion as [`compare_values`] (so `"10" == 10`). Values which are not
+/// comparable, like two nulls, are equal only if they are identical.
+fn values_equal(left: Value, right: Value) -> bool {
+    match compare_values(left, right) {
+        Some(ord) => ord.is_eq(),
+        None => left == right,
+    }
+}
+
+/// Orders two values for the filter comparison operators. Ints and floats
+/// compare numerically with each other, strings compare lexicographically,
+/// and a string compares numerically against a number only if the whole
+/// string parses as a number (so `"10" > 9` and `"10" == 10`).
+/// Any other combination is not comparable and the ordering is false.
+pub(crate) fn compare_values(left: Value, right: Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Str(l), Value::Str(r)) => Some(l.cmp(r)),
+        (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(&r)),
+        (l, r) => match (as_number(l)?, as_number(r)?) {
+            (Number::Int(l), Number::Int(r)) => Some(l.cmp(&r)),
+            (l, r) => l.as_f64().partial_cmp(&r.as_f64()),
+        },
+    }
+}
+
+/// Computes the result of a filter function over the values of the cells
+/// matched by its argument. `count` counts the cells and `len` measures the
+/// first value; the numeric functions skip values which are not numbers.
+/// A function with nothing to work on (e.g. `max` of no numbers) has no
+/// value, which makes any comparison with it false.
+fn aggregate(function: Function, values: &[OwnValue]) -> OwnValue {
+    let numbers = || values.iter().filter_map(|v| as_number(v.as_value()));
+    match function {
+        Function::Count => OwnValue::Int(Int::from(values.len())),
+        Function::Len => match values.first() {
+            None | Some(OwnValue::None) => OwnValue::None,
+            Some(v) => OwnValue::Int(Int::from(v.as_value().as_cow_str().chars().count())),
+        },
+        Function::Sum => numbers()
+            .fold(Number::Int(0), |acc, n| match (acc, n) {
+                (Number::Int(a), Number::Int(b)) => Number::Int(a.saturating_add(b)),
+                (a, b) => Number::Float(a.as_f64() + b.as_f64()),
+            })
+            .into(),
+        Function::Min | Function::Max => numbers()
+            .reduce(|acc, n| {
+                let ord = match (acc, n) {
+                    (Number::Int(a), Number::Int(b)) => a.cmp(&b),
+                    (a, b) => a.as_f64().total_cmp(&b.as_f64()),
+                };
+                let keep_acc = if function == Function::Min {
+                    ord.is_le()
+                } else {
+                    ord.is_ge()
+                };
+                if keep_acc { acc } else { n }
+            })
+            .map(OwnValue::from)
+            .unwrap_or_default(),
+        Function::Avg => {
+            let (count, sum) = numbers().fold((0, 0.0), |(c, s), n| (c + 1, s + n.as_f64()));
+            if count == 0 {
+                OwnValue::None
+            } else {
+                OwnValue::Float(StrFloat(sum / count as f64))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+impl From<Number> for OwnValue {
+    fn from(n: Number) -> OwnValue {
+        match n {
+            Number::Int(i) => match i64::try_from(i) {
+                Ok(i) => OwnValue::Int(Int::from(i)),
+                Err(_) => OwnValue::Float(StrFloat(i as f64)),
+            },
+            Number::Float(f) => OwnValue::Float(StrFloat(f)),
+        }
+    }
+}
+
+fn as_number(v: Value) -> Option<Number> {
+    match v {
+        Value::Int(i) => Some(Number::Int(i.as_i128())),
+        Value::Float(f) => Some(Number::Float(f.0)),
+        Value::Str(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i128>() {
+                Some(Number::Int(i))
+            } else {
+                s.parse::<f64>().ok().map(Number::Float)
+            }
+        }
+        _ => None,
+    }
+}
+
 impl<'s> Iterator for Searcher<'s> {
     type Item = Res<Xell>;
     fn next(&mut self) -> Option<Res<Xell>> {
-        self.eval_next()
+        self.next_with_captures()
+            .map(|found| found.map(|(cell, _)| cell))
     }
 }
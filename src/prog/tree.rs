@@ -0,0 +1,188 @@
+use serde_json::{Map, Value as SValue};
+
+use crate::{api::*, interpretations::json::ownvalue_to_serde, prog::searcher::Captures};
+
+/// Builds an in-memory json tree from search matches and their captures.
+/// Each capture adds two levels of objects, the first keyed by the capture
+/// name and the second by the path of the captured cell, e.g.
+/// `{"svc": {"./compose.yaml^yaml/services/web": [80, 443]}}`. The matched
+/// cells are collected in arrays at the innermost level, or under the `.` key
+/// if the same level also has captures. Without captures the tree is a flat
+/// array of the matched cells.
+pub(crate) fn result_tree(matches: impl IntoIterator<Item = Res<(Xell, Captures)>>) -> Res<Xell> {
+    let mut root = SValue::Null;
+    for found in matches {
+        let (cell, captures) = found?;
+        let mut node = &mut root;
+        for (name, captured) in &captures {
+            let names = object_node(node);
+            let SValue::Object(paths) = names
+                .entry(name.clone())
+                .or_insert_with(|| SValue::Object(Map::new()))
+            else {
+                return fault(format!("capture `{}` does not fit the result tree", name));
+            };
+            node = paths.entry(capture_key(captured)?).or_insert(SValue::Null);
+        }
+        if let SValue::Object(map) = node {
+            node = map
+                .entry(MATCHES_KEY)
+                .or_insert_with(|| SValue::Array(vec![]));
+        }
+        if node.is_null() {
+            *node = SValue::Array(vec![]);
+        }
+        let SValue::Array(leaves) = node else {
+            return fault("matched cell does not fit the result tree");
+        };
+        leaves.push(cell_to_serde(&cell)?);
+    }
+    if root.is_null() {
+        root = SValue::Array(vec![]);
+    }
+    let json = serde_json::to_string(&root)
+        .map_err(|e| caused(HErrKind::Internal, "cannot serialize result tree", e))?;
+    Xell::from(json).be("json").err()
+}
+
+// capture names are identifiers, so they never clash with this key
+const MATCHES_KEY: &str = ".";
+
+// turns a node into an object, keeping the matches it already holds
+fn object_node(node: &mut SValue) -> &mut Map<String, SValue> {
+    if !node.is_object() {
+        let mut map = Map::new();
+        if let SValue::Array(leaves) = node.take() {
+            map.insert(MATCHES_KEY.to_string(), SValue::Array(leaves));
+        }
+        *node = SValue::Object(map);
+    }
+    match node {
+        SValue::Object(map) => map,
+        _ => unreachable!("node was just made an object"),
+    }
+}
+
+/// The key of a captured cell in the result tree: the file path for files,
+/// otherwise the key of the origin of its domain followed by its path in the
+/// domain. Labels are used where they are unique and indexes elsewhere, so
+/// different cells never share a key.
+fn capture_key(cell: &Xell) -> Res<String> {
+    if let Ok(path) = cell.read().as_file_path() {
+        return Ok(path.to_string_lossy().into_owned());
+    }
+    let mut items = vec![];
+    let mut current = cell.clone();
+    loop {
+        match current.head() {
+            Ok((head, relation)) => {
+                items.push(format!(
+                    "{}{}",
+                    relation,
+                    step_key(&head, relation, &current)?
+                ));
+                current = head;
+            }
+            Err(e) if e.kind == HErrKind::None => break,
+            Err(e) => return Err(e),
+        }
+    }
+    let origin = match current.parent() {
+        Ok(origin) => format!("{}^{}", capture_key(&origin)?, cell.interpretation()),
+        Err(e) if e.kind == HErrKind::None => String::new(),
+        Err(e) => return Err(e),
+    };
+    items.reverse();
+    Ok(origin + &items.concat())
+}
+
+// the label of a cell in its group, followed by its position among the cells
+// with the same label if there are several, as in `a[1]`
+fn step_key(head: &Xell, relation: Relation, cell: &Xell) -> Res<String> {
+    let reader = cell.read().err()?;
+    let index = reader.index()?;
+    let label = match reader.label() {
+        Ok(label) if !label.is_empty() => label.to_owned_value(),
+        _ => return Ok(format!("[{}]", index)),
+    };
+    let group = match relation {
+        Relation::Attr => head.attr(),
+        Relation::Field => head.field(),
+        _ => head.sub(),
+    };
+    if group.label_type().unique_labels {
+        return Ok(label.to_string());
+    }
+    let mut position = None;
+    let mut count = 0;
+    for same in group.get_all(label.as_value()) {
+        if same.read().index().ok() == Some(index) {
+            position = Some(count);
+        }
+        count += 1;
+    }
+    match position {
+        Some(position) if count > 1 => Ok(format!("{}[{}]", label, position)),
+        _ => Ok(label.to_string()),
+    }
+}
+
+/// Converts a cell and its subtree to json: groups with unique labels become
+/// objects, other groups become arrays and leaves become scalar values; bytes
+/// become a string, with invalid utf-8 replaced. Only the sub cells are
+/// followed, so the subtree never leaves the interpretation of the cell.
+pub(crate) fn cell_to_serde(cell: &Xell) -> Res<SValue> {
+    let group = match cell.sub().err() {
+        Ok(group) => Some(group),
+        Err(e) if e.kind == HErrKind::None => None,
+        Err(e) => return Err(e),
+    };
+    let Some(group) = group.filter(|g| !g.is_empty()) else {
+        let reader = cell.read().err()?;
+        return match (reader.ty()?, reader.value()) {
+            ("array", _) => Ok(SValue::Array(vec![])),
+            ("object" | "table", _) => Ok(SValue::Object(Map::new())),
+            (_, Ok(Value::Bytes)) => {
+                let mut bytes = vec![];
+                reader
+                    .value_read()?
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| caused(HErrKind::IO, "cannot read bytes", e))?;
+                Ok(SValue::String(String::from_utf8_lossy(&bytes).into_owned()))
+            }
+            (_, Ok(value)) => Ok(ownvalue_to_serde(value.to_owned_value())),
+            (_, Err(e)) if e.kind == HErrKind::None => Ok(SValue::Null),
+            (_, Err(e)) => Err(e),
+        };
+    };
+    let mut children = vec![];
+    for child in group.clone() {
+        let child = child.err()?;
+        let label = match child.read().label() {
+            Ok(Value::None) | Err(_) => None,
+            Ok(Value::Str(s)) => Some(s.to_string()),
+            Ok(label) => Some(label.to_string()),
+        };
+        children.push((label, cell_to_serde(&child)?));
+    }
+    if group.label_type().unique_labels && children.iter().all(|(label, _)| label.is_some()) {
+        Ok(SValue::Object(
+            children
+                .into_iter()
+                .filter_map(|(label, value)| Some((label?, value)))
+                .collect(),
+        ))
+    } else {
+        // labelled cells in a group with repeated labels (e.g. xml elements)
+        // keep their label as a single entry object
+        Ok(SValue::Array(
+            children
+                .into_iter()
+                .map(|(label, value)| match label {
+                    Some(label) => SValue::Object(Map::from_iter([(label, value)])),
+                    None => value,
+                })
+                .collect(),
+        ))
+    }
+}
@@ -52,6 +52,49 @@ fn search_path_with_fs_starter() -> Res<()> {
     Ok(())
 }
 
+#[test]
+fn fs_glob_labels() -> Res<()> {
+    let dir = std::env::temp_dir().join(format!("hial-fs-glob-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(|e| caused(HErrKind::IO, "cannot create dir", e))?;
+    for name in [
+        "main.rs",
+        "pa.rs",
+        "path.rs",
+        "search.rs",
+        "split.rs",
+        "text.rs",
+        "toml.rs",
+        "xml.rs",
+        "zz.md",
+    ] {
+        std::fs::write(dir.join(name), "x")
+            .map_err(|e| caused(HErrKind::IO, "cannot seed file", e))?;
+    }
+    let dir_str = dir.to_string_lossy().to_string();
+
+    let folder = Xell::from(dir_str.as_str()).be("path").be("fs").sub();
+    let range = folder.label_prefix_range("pa")?;
+    let labels = range
+        .map(|i| Ok(folder.at(i).read().label()?.to_string()))
+        .collect::<Res<Vec<_>>>()?;
+    assert_eq!(labels, ["pa.rs", "path.rs"]);
+
+    let eval = Xell::from(dir_str.as_str())
+        .be("path")
+        .be("fs")
+        .all("/{s*.rs,t?xt.rs,*[ml].rs}")?
+        .into_iter()
+        .map(|c| Ok(c.read().label()?.to_string()))
+        .collect::<Res<Vec<_>>>()?;
+    assert_eq!(
+        eval,
+        ["search.rs", "split.rs", "text.rs", "toml.rs", "xml.rs"]
+    );
+
+    std::fs::remove_dir_all(&dir).map_err(|e| caused(HErrKind::IO, "cannot remove dir", e))?;
+    Ok(())
+}
+
 #[test]
 fn fs_write_prog_policy() -> Res<()> {
     let p = "^path^fs/src/tests/data/write.txt";
@@ -81,6 +124,28 @@ fn fs_write_path_policy() -> Res<()> {
     Ok(())
 }
 
+#[test]
+fn fs_delete() -> Res<()> {
+    let p = "./src/tests/data/delete.txt";
+    std::fs::write(p, "x").map_err(|e| caused(HErrKind::IO, "cannot seed file", e))?;
+    let data = Xell::new(".^fs[w]/src/tests/data").err()?;
+    let count = data.sub().len()?;
+    data.to("/delete.txt").write().detach()?;
+    assert_eq!(data.sub().len()?, count - 1);
+
+    let err = data
+        .to("/delete.txt")
+        .err()
+        .expect_err("file should be deleted");
+    assert_eq!(err.kind, HErrKind::None);
+
+    // the file is removed from disk when the domain is saved
+    assert!(std::path::Path::new(p).exists());
+    drop((data, err));
+    assert!(!std::path::Path::new(p).exists());
+    Ok(())
+}
+
 #[test]
 fn fs_path() -> Res<()> {
     let c = Xell::from(".")
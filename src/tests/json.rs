@@ -153,3 +153,44 @@ fn json_write_and_save() -> Res<()> {
 
     Ok(())
 }
+
+#[test]
+fn json_delete() -> Res<()> {
+    let text =
+        Xell::from(r#"{"a": 1, "b": [1, 2, 3], "c": {"d": 4}}"#).policy(WritePolicy::NoAutoWrite);
+    let json = text.be("json");
+    json.to("/b/[1]").write().detach()?;
+    json.to("/a").write().detach()?;
+    assert_eq!(json.to("/c").read().index()?, 1);
+
+    json.save(&json.origin())?;
+    assert_eq!(text.read().value()?, r#"{"b":[1,3],"c":{"d":4}}"#);
+
+    let err = json
+        .write()
+        .detach()
+        .expect_err("root cell cannot be deleted");
+    assert_eq!(err.kind, HErrKind::Input);
+    Ok(())
+}
+
+#[test]
+fn json_create_and_add() -> Res<()> {
+    let text = Xell::from(r#"{"a": [1]}"#).policy(WritePolicy::NoAutoWrite);
+    let json = text.be("json");
+    let root = json.sub().err()?;
+    root.add(Some(0), root.create(Some("b".into()), Some(2.into()))?)?;
+    let list = json.to("/a").sub().err()?;
+    list.add(None, list.create(None, None)?)?;
+    let empty = list.create(None, None)?;
+    empty.write().ty("array")?;
+    list.add(None, empty)?;
+    assert!(
+        root.add(None, root.create(Some("a".into()), None)?)
+            .is_err()
+    );
+
+    json.save(&json.origin())?;
+    assert_eq!(text.read().value()?, r#"{"b":2,"a":[1,{},[]]}"#);
+    Ok(())
+}
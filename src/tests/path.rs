@@ -30,6 +30,17 @@ fn test_parse_paths() -> Res<()> {
         assert_eq!(path.to_string(), "^json");
     }
 
+    {
+        let (start, path) = Path::parse_with_starter("-^json/items/*")?;
+        assert_eq!(start, PathStart::Stdin);
+        assert_eq!(format!("{}{}", start, path), "-^json/items/*");
+
+        // without an elevation, `-` is a file name
+        let (start, path) = Path::parse_with_starter("-x")?;
+        assert_eq!(start, PathStart::File("-x".to_string()));
+        assert_eq!(path.to_string(), "");
+    }
+
     {
         let (start, path) = Path::parse_with_starter("http://localhost")?;
         assert_eq!(start, PathStart::Url(Url::parse("http://localhost")?));
@@ -113,8 +124,9 @@ fn path_simple_selector() -> Res<()> {
         &[PathItem::Normal(NormalPathItem {
             relation: Relation::Sub,
             selector: Some(Selector::Str("a")),
-            index: Some(2),
+            index: Some(Index::At(2)),
             filters: vec![],
+            capture: None,
         })]
     );
 
@@ -124,8 +136,9 @@ fn path_simple_selector() -> Res<()> {
         &[PathItem::Normal(NormalPathItem {
             relation: Relation::Sub,
             selector: Some(Selector::Str("a")),
-            index: Some(-2),
+            index: Some(Index::At(-2)),
             filters: vec![],
+            capture: None,
         }),]
     );
     Ok(())
@@ -145,6 +158,7 @@ fn path_simple_type_expr() -> Res<()> {
                     ty: "fn_item0".to_string()
                 }
             }],
+            capture: None,
         })]
     );
     Ok(())
@@ -167,8 +181,9 @@ fn path_ternary_expr() -> Res<()> {
                             selector: Some(Selector::Str("attr")),
                             index: None,
                             filters: vec![],
+                            capture: None,
                         })]),
-                        op_right: Some(("==", OwnValue::Int(1.into())))
+                        op_right: Some(("==", OwnValue::Int(1.into()).into()))
                     }
                 },
                 Filter {
@@ -178,11 +193,13 @@ fn path_ternary_expr() -> Res<()> {
                             selector: Some(Selector::Str("x")),
                             index: None,
                             filters: vec![],
+                            capture: None,
                         })]),
                         op_right: None,
                     }
                 }
             ],
+            capture: None,
         })]
     );
 
@@ -200,15 +217,119 @@ fn path_ternary_expr() -> Res<()> {
                         selector: Some(Selector::Str("buildVersion")),
                         index: None,
                         filters: vec![],
+                        capture: None,
                     })]),
-                    op_right: Some(("==", OwnValue::String("dev".to_string())))
+                    op_right: Some(("==", OwnValue::String("dev".to_string()).into()))
                 }
             }],
+            capture: None,
         })]
     );
     Ok(())
 }
 
+#[test]
+fn path_ordering_ops() -> Res<()> {
+    for op in ["<", "<=", ">", ">="] {
+        let input = format!("/a[@status/code{}400]", op);
+        let path = Path::parse(&input)?;
+        let PathItem::Normal(npi) = &path.0[0] else {
+            panic!("expected normal path item");
+        };
+        let Expression::Ternary { op_right, .. } = &npi.filters[0].expr else {
+            panic!("expected ternary expression");
+        };
+        assert_eq!(op_right, &Some((op, OwnValue::Int(400.into()).into())));
+    }
+    Ok(())
+}
+
+#[test]
+fn path_filter_value_literals() -> Res<()> {
+    for (literal, value) in [
+        ("1.5", OwnValue::Float(StrFloat(1.5))),
+        ("-0.25", OwnValue::Float(StrFloat(-0.25))),
+        ("true", OwnValue::Bool(true)),
+        ("false", OwnValue::Bool(false)),
+        ("null", OwnValue::None),
+        ("trueish", OwnValue::String("trueish".to_string())),
+    ] {
+        let input = format!("/a[/b=={}]", literal);
+        let path = Path::parse(&input)?;
+        let PathItem::Normal(npi) = &path.0[0] else {
+            panic!("expected normal path item");
+        };
+        let Expression::Ternary { op_right, .. } = &npi.filters[0].expr else {
+            panic!("expected ternary expression");
+        };
+        assert_eq!(op_right, &Some(("==", value.into())));
+    }
+    Ok(())
+}
+
+#[test]
+fn path_regex_op() -> Res<()> {
+    let path = Path::parse(r"/*[#label~='.*_test\.rs$']")?;
+    let PathItem::Normal(npi) = &path.0[0] else {
+        panic!("expected normal path item");
+    };
+    let Expression::Ternary { op_right, .. } = &npi.filters[0].expr else {
+        panic!("expected ternary expression");
+    };
+    match op_right {
+        Some(("~=", Operand::Regex(regex))) => assert_eq!(regex.0.as_str(), r".*_test\.rs$"),
+        _ => panic!("expected a compiled regex operand"),
+    }
+
+    assert!(Path::parse("/*[#label~='(']").is_err());
+    assert!(Path::parse("/*[#label~=3]").is_err());
+    Ok(())
+}
+
+#[test]
+fn path_word_ops_as_fields() -> Res<()> {
+    let filter_of = |path: &'static str| -> Res<Expression<'static>> {
+        let path = Path::parse(path)?;
+        let PathItem::Normal(npi) = &path.0[0] else {
+            panic!("expected normal path item");
+        };
+        Ok(npi.filters[0].expr.clone())
+    };
+    let field = |name: &'static str| {
+        Path(vec![PathItem::Normal(NormalPathItem {
+            relation: Relation::Field,
+            selector: Some(Selector::Str(name)),
+            index: None,
+            filters: vec![],
+            capture: None,
+        })])
+    };
+
+    // in operator position the word is an operation
+    let Expression::Ternary { left, op_right } = filter_of("/a[contains 'x']")? else {
+        panic!("expected ternary expression");
+    };
+    assert_eq!(left, Path(vec![]));
+    assert_eq!(op_right, Some(("contains", OwnValue::from("x").into())));
+
+    // elsewhere it is a field name
+    let Expression::Ternary { left, op_right } = filter_of("/a[contains]")? else {
+        panic!("expected ternary expression");
+    };
+    assert_eq!(left, field("contains"));
+    assert_eq!(op_right, None);
+
+    let Expression::Ternary { left, op_right } = filter_of("/a[endswith == 'x']")? else {
+        panic!("expected ternary expression");
+    };
+    assert_eq!(left, field("endswith"));
+    assert_eq!(op_right, Some(("==", OwnValue::from("x").into())));
+
+    // a word operation must end at a word boundary
+    assert!(Path::parse("/a[#label containsx 'a']").is_err());
+    Ok(())
+}
+
 #[test]
 fn path_simple_or_expr() -> Res<()> {
     let path = Path::parse("/a[:x|:y|:z]")?;
@@ -233,11 +354,229 @@ fn path_simple_or_expr() -> Res<()> {
                     ]
                 }
             }],
+            capture: None,
         })]
     );
     Ok(())
 }
 
+#[test]
+fn path_bool_expr_precedence() -> Res<()> {
+    let ty = |t: &str| Expression::Type { ty: t.to_string() };
+    fn filter_expr(input: &str) -> Res<Expression<'_>> {
+        let path = Path::parse(input)?;
+        let PathItem::Normal(npi) = path.0.into_iter().next().unwrap() else {
+            panic!("expected normal path item");
+        };
+        Ok(npi.filters.into_iter().next().unwrap().expr)
+    }
+
+    assert_eq!(
+        filter_expr("/a[:x | :y & !:z]")?,
+        Expression::Or {
+            expressions: vec![
+                ty("x"),
+                Expression::And {
+                    expressions: vec![
+                        ty("y"),
+                        Expression::Not {
+                            expression: Box::new(ty("z"))
+                        }
+                    ]
+                }
+            ]
+        }
+    );
+    assert_eq!(
+        filter_expr("/a[(:x|:y)&:z]")?,
+        Expression::And {
+            expressions: vec![
+                Expression::Or {
+                    expressions: vec![ty("x"), ty("y")]
+                },
+                ty("z")
+            ]
+        }
+    );
+    assert_eq!(
+        filter_expr("/a[!(:x&:y)]")?.to_string(),
+        "!(:x&:y)".to_string()
+    );
+    assert!(matches!(
+        filter_expr("/a[!='x']")?,
+        Expression::Ternary {
+            op_right: Some(("!=", _)),
+            ..
+        }
+    ));
+    assert!(Path::parse("/a[(:x]").is_err());
+    Ok(())
+}
+
+#[test]
+fn path_call_expressions() -> Res<()> {
+    let path = Path::parse("/question[count(/answer_entities/*)==0]")?;
+    let PathItem::Normal(npi) = &path.0[0] else {
+        panic!("expected normal path item");
+    };
+    assert_eq!(
+        npi.filters[0].expr,
+        Expression::Call {
+            function: Function::Count,
+            arg: Path::parse("/answer_entities/*")?,
+            op_right: Some(("==", OwnValue::from(0).into())),
+        }
+    );
+
+    let path = Path::parse("/a[avg(/*#value) >= 2 & len(/name)]")?;
+    assert_eq!(path.to_string(), "/a[avg(/*#value)>=Int(2i64)&len(/name)]");
+
+    // a field named like a function is still a field
+    let path = Path::parse("/a[/count]")?;
+    assert_eq!(path.to_string(), "/a[/count]");
+
+    assert!(Path::parse("/a[max()]").is_err());
+    Ok(())
+}
+
+#[test]
+fn path_index_ranges() -> Res<()> {
+    let index = |input: &str| -> Res<Option<Index>> {
+        let path = Path::parse(input)?;
+        let PathItem::Normal(npi) = &path.0[0] else {
+            panic!("expected normal path item");
+        };
+        Ok(npi.index)
+    };
+    assert_eq!(index("/a[2..5]")?, Some(Index::Range(Some(2), Some(5))));
+    assert_eq!(index("/*[..10]")?, Some(Index::Range(None, Some(10))));
+    assert_eq!(index("/[-3..]")?, Some(Index::Range(Some(-3), None)));
+    assert_eq!(index("/a[..]")?, Some(Index::Range(None, None)));
+    assert_eq!(
+        Path::parse("/a[-3..]/b[..10]")?.to_string(),
+        "/a[-3..]/b[..10]"
+    );
+
+    assert_eq!(Index::range(Some(2), Some(5), 10), 2..5);
+    assert_eq!(Index::range(None, Some(-1), 10), 0..9);
+    assert_eq!(Index::range(Some(-3), None, 10), 7..10);
+    assert_eq!(Index::range(Some(-30), Some(30), 10), 0..10);
+    assert_eq!(Index::range(Some(5), Some(2), 10).len(), 0);
+    Ok(())
+}
+
+#[test]
+fn path_label_alternatives() -> Res<()> {
+    let path = Path::parse("/a/{name, image,ports}[0]")?;
+    let PathItem::Normal(npi) = &path.0[1] else {
+        panic!("expected normal path item");
+    };
+    assert_eq!(npi.selector, Some(Selector::Alt("name, image,ports")));
+    assert_eq!(npi.index, Some(Index::At(0)));
+    assert_eq!(
+        npi.selector.unwrap().alternatives().collect::<Vec<_>>(),
+        ["name", "image", "ports"]
+    );
+    assert!(Selector::Alt("name,image") == "image");
+    assert!(Selector::Alt("name,image") != "ports");
+    assert!(Path::parse("/a/{}").is_err());
+    Ok(())
+}
+
+#[test]
+fn path_glob_selectors() -> Res<()> {
+    fn selector(input: &str) -> Res<Option<Selector<'_>>> {
+        let path = Path::parse(input)?;
+        let PathItem::Normal(npi) = &path.0[0] else {
+            panic!("expected normal path item");
+        };
+        Ok(npi.selector)
+    }
+    assert_eq!(selector("/*.yaml")?, Some(Selector::Glob("*.yaml")));
+    assert_eq!(selector("/config_?")?, Some(Selector::Glob("config_?")));
+    assert_eq!(
+        selector("/log[0-9].txt")?,
+        Some(Selector::Glob("log[0-9].txt"))
+    );
+    assert_eq!(selector("/*")?, Some(Selector::Star));
+    assert_eq!(selector("/***")?, Some(Selector::TripleStar));
+    // a trailing bracket is an index or a filter
+    assert_eq!(selector("/log*[0]")?, Some(Selector::Glob("log*")));
+    assert_eq!(selector("/log[x]")?, Some(Selector::Str("log")));
+
+    assert!(glob_match("*.yaml", "docker-compose.yaml"));
+    assert!(!glob_match("*.yaml", "docker-compose.yml"));
+    assert!(glob_match("config_?", "config_a"));
+    assert!(!glob_match("config_?", "config_ab"));
+    assert!(glob_match("a*b*c", "aXbYbZc"));
+    assert!(glob_match("[abc]x", "bx"));
+    assert!(glob_match("[a-c]x", "cx"));
+    assert!(!glob_match("[!a-c]x", "cx"));
+    assert!(glob_match("[]]", "]"));
+    assert!(glob_match("*", ""));
+    assert_eq!(glob_prefix("config_*.yaml"), "config_");
+    Ok(())
+}
+
+#[test]
+fn path_parent_items() -> Res<()> {
+    let path = Path::parse("/a/..**[#label=='x']/../b")?;
+    assert_eq!(
+        path.0[1],
+        PathItem::Parent(ParentPathItem {
+            ancestors: true,
+            filters: vec![Filter {
+                expr: Expression::Ternary {
+                    left: Path::parse("#label")?,
+                    op_right: Some(("==", OwnValue::from("x".to_string()).into())),
+                },
+            }],
+        })
+    );
+    assert_eq!(
+        path.0[2],
+        PathItem::Parent(ParentPathItem {
+            ancestors: false,
+            filters: vec![],
+        })
+    );
+    assert_eq!(
+        Path::parse("/a/..**[#label]/../b")?.to_string(),
+        "/a/..**[#label]/../b"
+    );
+
+    // a label starting with dots is not a parent item
+    let path = Path::parse("/..a")?;
+    assert!(matches!(path.0[0], PathItem::Normal(_)));
+    Ok(())
+}
+
+#[test]
+fn path_captures() -> Res<()> {
+    let path = Path::parse("/services/*[/image][as svc]/ports[0][ as port ]")?;
+    let PathItem::Normal(svc) = &path.0[1] else {
+        panic!("normal path item expected");
+    };
+    assert_eq!(svc.capture, Some("svc"));
+    assert_eq!(svc.filters.len(), 1);
+    let PathItem::Normal(port) = &path.0[2] else {
+        panic!("normal path item expected");
+    };
+    assert_eq!(port.index, Some(Index::At(0)));
+    assert_eq!(port.capture, Some("port"));
+    assert_eq!(
+        path.to_string(),
+        "/services/*[/image][as svc]/ports[0][as port]"
+    );
+
+    // the capture may come before the filters
+    let path = Path::parse("/a[as x][/b]")?;
+    assert_eq!(path.to_string(), "/a[/b][as x]");
+
+    assert!(Path::parse("/a[as x][as y]").is_err());
+    Ok(())
+}
+
 #[test]
 fn path_items() -> Res<()> {
     let path = Path::parse("/a@name/[2]/*[#value=='3'][/x]")?;
@@ -249,18 +588,21 @@ fn path_items() -> Res<()> {
                 selector: Some("a".into()),
                 index: None,
                 filters: vec![],
+                capture: None,
             }),
             PathItem::Normal(NormalPathItem {
                 relation: Relation::Attr,
                 selector: Some("name".into()),
                 index: None,
                 filters: vec![],
+                capture: None,
             }),
             PathItem::Normal(NormalPathItem {
                 relation: Relation::Sub,
                 selector: None,
-                index: Some(2),
+                index: Some(Index::At(2)),
                 filters: vec![],
+                capture: None,
             }),
             PathItem::Normal(NormalPathItem {
                 relation: Relation::Sub,
@@ -274,8 +616,9 @@ fn path_items() -> Res<()> {
                                 selector: Some("value".into()),
                                 index: None,
                                 filters: vec![],
+                                capture: None,
                             }),]),
-                            op_right: Some(("==", OwnValue::String("3".to_string())))
+                            op_right: Some(("==", OwnValue::String("3".to_string()).into()))
                         }
                     },
                     Filter {
@@ -285,11 +628,13 @@ fn path_items() -> Res<()> {
                                 selector: Some("x".into()),
                                 index: None,
                                 filters: vec![],
+                                capture: None,
                             }),]),
                             op_right: None
                         }
                     }
                 ],
+                capture: None,
             })
         ]
     );
@@ -355,3 +700,30 @@ fn path_parts_write_add_extension() -> Res<()> {
     assert_eq!(path.read().value()?, "./LICENSE.txt");
     Ok(())
 }
+
+#[test]
+fn path_operand_paths() -> Res<()> {
+    let path = Path::parse("/*[/image == $release/image][/owner==$users/*/id]")?;
+    assert_eq!(
+        path.to_string(),
+        "/*[/image==$release/image][/owner==$users/*/id]"
+    );
+    assert_eq!(path.variables(), ["release", "users"]);
+
+    let path = Path::parse("/*[/tag != /default][count(/x) < $limits/x]")?;
+    let PathItem::Normal(npi) = &path.0[0] else {
+        panic!("normal path item expected");
+    };
+    assert!(matches!(
+        &npi.filters[0].expr,
+        Expression::Ternary {
+            op_right: Some(("!=", Operand::Path(_))),
+            ..
+        }
+    ));
+    assert_eq!(path.variables(), ["limits"]);
+
+    // the regex operand must still be a literal string
+    assert!(Path::parse("/*[/a ~= /b]").is_err());
+    Ok(())
+}
@@ -1,6 +1,11 @@
 use crate::{
     api::*,
-    prog::{Path, PathStart, Program, ProgramParams, program::Statement},
+    config::ColorPalette,
+    pprint::diff_lines,
+    prog::{
+        ExecutionContext, Path, PathStart, Program, ProgramParams, program::Statement,
+        searcher::Searcher, tree,
+    },
     utils::log::set_verbose,
 };
 use std::fs;
@@ -14,8 +19,7 @@ fn program_simple_path() -> Res<()> {
             assert_eq!(start, &PathStart::File(".".to_string()));
             assert_eq!(path, &Path::parse("^regex[a]")?);
         }
-        Statement::Assignment(_, _, _) => panic!("Expected a path statement!"),
-        Statement::VarBind(_, _, _) => panic!("Expected a path statement!"),
+        _ => panic!("Expected a path statement!"),
     }
     Ok(())
 }
@@ -89,6 +93,11 @@ fn program_undefined_variable_errors() -> Res<()> {
         .run(ProgramParams::default())
         .expect_err("expected undefined variable error");
     assert!(format!("{}", err).contains("undefined variable :missing"));
+
+    let err = Program::parse("./src/tests/data/assignment.json^json/*[/a == $missing/a]")?
+        .run(ProgramParams::default())
+        .expect_err("expected undefined variable error");
+    assert!(format!("{}", err).contains("undefined variable :missing"));
     Ok(())
 }
 
@@ -124,3 +133,868 @@ fn program_allows_newline_statement_separator() -> Res<()> {
     )?;
     Ok(())
 }
+
+#[test]
+fn program_tree() -> Res<()> {
+    let prog = Program::parse("tree ./src/tests/data/assignment.json^json/*[as key]")?;
+    match &prog.0[0] {
+        Statement::Tree(start, path) => {
+            assert_eq!(
+                start,
+                &PathStart::File("./src/tests/data/assignment.json".to_string())
+            );
+            assert_eq!(path, &Path::parse("^json/*[as key]")?);
+        }
+        _ => panic!("Expected a tree statement!"),
+    }
+    assert_eq!(
+        prog.to_string(),
+        "tree ./src/tests/data/assignment.json^json/*[as key]\n"
+    );
+
+    const TREE: &str = r#"{
+        "services": {
+            "web": {"image": "nginx", "ports": [80, 443]},
+            "db": {"image": "postgres", "ports": [5432]}
+        }
+    }"#;
+    let root = Xell::from(TREE).be("json");
+    let tree = result_tree(Path::parse("/services/*[as svc]/ports/*")?.eval(root.clone()))?;
+    assert_eq!(
+        tree.read().serial()?,
+        r#"{"svc":{"^json/services/web":[80,443],"^json/services/db":[5432]}}"#
+    );
+
+    let tree = result_tree(Path::parse("/services/*[#label=='web']")?.eval(root.clone()))?;
+    assert_eq!(
+        tree.read().serial()?,
+        r#"[{"image":"nginx","ports":[80,443]}]"#
+    );
+
+    // matches with and without captures share the tree
+    let web = root.to("/services/web");
+    let matches = vec![
+        Ok((web.to("/image"), vec![])),
+        Ok((web.to("/ports/[0]"), vec![("svc".to_string(), web.clone())])),
+    ];
+    assert_eq!(
+        tree::result_tree(matches)?.read().serial()?,
+        r#"{".":["nginx"],"svc":{"^json/services/web":[80]}}"#
+    );
+
+    // repeated labels are told apart by their position
+    let xml = Xell::from("<r><a>1</a><b>2</b><a>3</a></r>").be("xml");
+    let tree = result_tree(Path::parse("/r/*[as el]")?.eval(xml))?;
+    assert_eq!(
+        tree.read().serial()?,
+        r#"{"el":{"^xml/r/a[0]":["1"],"^xml/r/b":["2"],"^xml/r/a[1]":["3"]}}"#
+    );
+    Ok(())
+}
+
+#[test]
+fn program_tree_same_labels() -> Res<()> {
+    let dir = std::env::temp_dir().join(format!("hial-tree-{}", std::process::id()));
+    for (name, image) in [("a", "nginx"), ("b", "postgres")] {
+        fs::create_dir_all(dir.join(name)).expect("failed to create compose dir");
+        fs::write(
+            dir.join(name).join("config.yaml"),
+            format!("services:\n  main:\n    image: {}\n", image),
+        )
+        .expect("failed to seed config.yaml");
+    }
+    let dir = dir.to_string_lossy().to_string();
+
+    let program = format!(
+        "{}^fs/*/config.yaml[as composefile]^yaml/services/*/image",
+        dir
+    );
+    let (start, path) = Path::parse_with_starter(&program)?;
+    let tree = result_tree(path.eval(start.eval()?))?;
+    assert_eq!(
+        tree.read().serial()?,
+        format!(
+            r#"{{"composefile":{{"{0}/a/config.yaml":["nginx"],"{0}/b/config.yaml":["postgres"]}}}}"#,
+            dir
+        )
+    );
+
+    fs::remove_dir_all(&dir).expect("failed to cleanup compose dirs");
+    Ok(())
+}
+
+fn result_tree(mut searcher: Searcher) -> Res<Xell> {
+    tree::result_tree(std::iter::from_fn(|| searcher.next_with_captures()))
+}
+
+#[test]
+fn program_copy() -> Res<()> {
+    let prog = Program::parse("copy ./a.json^json/x  $cfg/y")?;
+    assert_eq!(prog.to_string(), "copy ./a.json^json/x $cfg/y\n");
+
+    let test_file = "./src/tests/data/copy_destination.json";
+    fs::write(
+        test_file,
+        r#"{"a": {"x": 1, "y": {"z": 2}}, "b": {"x": 0, "y": {"z": 0}}, "c": [0]}"#,
+    )
+    .expect("failed to seed copy_destination.json");
+    Program::parse(
+        "$dst := ./src/tests/data/copy_destination.json^fs[w]^json\n\
+         copy $dst/a $dst/b\n\
+         copy $dst/a/x  $dst/c/[0]",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read copy_destination.json");
+    assert_eq!(
+        written,
+        r#"{"a":{"x":1,"y":{"z":2}},"b":{"x":1,"y":{"z":2}},"c":[1]}"#
+    );
+
+    // the missing cells are created in the destination
+    fs::write(
+        test_file,
+        r#"{"a": {"w": 1, "l": [1, {"v": 2}]}, "b": {}, "c": 0}"#,
+    )
+    .expect("failed to seed");
+    Program::parse(
+        "$dst := ./src/tests/data/copy_destination.json^fs[w]^json; copy $dst/a $dst/b",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read copy_destination.json");
+    assert_eq!(
+        written,
+        r#"{"a":{"w":1,"l":[1,{"v":2}]},"b":{"w":1,"l":[1,{"v":2}]},"c":0}"#
+    );
+
+    // a subtree cannot be copied into a leaf
+    let err = Program::parse(
+        "$dst := ./src/tests/data/copy_destination.json^fs[w]^json; copy $dst/a $dst/c",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected copy error");
+    assert!(format!("{}", err).contains("has no sub group"));
+
+    // several sources would overwrite each other
+    let err = Program::parse(
+        "$dst := ./src/tests/data/copy_destination.json^fs[w]^json; copy $dst/a/* $dst/c",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected copy error");
+    assert!(
+        err.data.msg.contains("matches more than one cell"),
+        "{:?}",
+        err
+    );
+
+    // objects and arrays cannot be copied into each other
+    fs::write(test_file, r#"{"o": {"a": 0}, "l": [7]}"#).expect("failed to seed");
+    let err = Program::parse(
+        "$dst := ./src/tests/data/copy_destination.json^fs[w]^json; copy $dst/o $dst/l",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected copy error");
+    assert!(err.data.msg.contains("an object, into"), "{:?}", err);
+    let err = Program::parse(
+        "$dst := ./src/tests/data/copy_destination.json^fs[w]^json; copy $dst/l $dst/o",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected copy error");
+    assert!(err.data.msg.contains("an array, into"), "{:?}", err);
+    let written = fs::read_to_string(test_file).expect("failed to read copy_destination.json");
+    assert_eq!(written, r#"{"o": {"a": 0}, "l": [7]}"#);
+
+    fs::remove_file(test_file).expect("failed to cleanup copy_destination.json");
+    Ok(())
+}
+
+#[test]
+fn program_diff() -> Res<()> {
+    let prog = Program::parse("diff ./a.json^json  ./b.yaml^yaml/x\n$d := diff $a $b")?;
+    assert_eq!(
+        prog.to_string(),
+        "diff ./a.json^json ./b.yaml^yaml/x\n$d := diff $a $b\n"
+    );
+
+    let left = Xell::from(r#"{"a": 1, "b": {"c": "x", "d": true}, "e": 2}"#).be("json");
+    let right = Xell::from(r#"{"a": 1, "b": {"c": "y", "d": true}, "f": 3}"#).be("json");
+    let lines = diff_lines(&left.diff(&right)?, ColorPalette::None)?;
+    assert_eq!(
+        lines,
+        vec![
+            "  object",
+            "    b:",
+            "-     c: x",
+            "+     c: y",
+            "-   e: 2",
+            "+   f: 3",
+        ]
+    );
+
+    let lines = diff_lines(&left.diff(&left)?, ColorPalette::None)?;
+    assert!(lines.is_empty());
+    Ok(())
+}
+
+#[test]
+fn program_dry_run() -> Res<()> {
+    let test_file = "./src/tests/data/dry_run.json";
+    let original = r#"{"a":1,"b":{"c":2}}"#;
+    fs::write(test_file, original).expect("failed to seed dry_run.json");
+
+    let params = ProgramParams {
+        dry_run: true,
+        ..ProgramParams::default()
+    };
+    Program::parse(
+        "$f := ./src/tests/data/dry_run.json^fs[w]^json\n\
+         $f/a = 5\n\
+         delete $f/b/c",
+    )?
+    .run(params.clone())?;
+    let written = fs::read_to_string(test_file).expect("failed to read dry_run.json");
+    assert_eq!(written, original);
+
+    // the pending writes are available through the api
+    transaction::begin(true)?;
+    let f = Xell::new("./src/tests/data/dry_run.json^fs[w]^json");
+    f.to("/a").write().value(5)?;
+    let writes = transaction::finish()?.writes;
+    assert_eq!(writes.len(), 1);
+    assert_eq!(writes[0].interpretation, "json");
+    assert_eq!(writes[0].serial, r#"{"a":5,"b":{"c":2}}"#);
+    let diff = writes[0].diff()?;
+    let a = diff.sub().at(0).sub().get("a");
+    assert_eq!(a.read().value()?, Value::from(5));
+    assert_eq!(
+        a.attr().get("diff_old_value").read().value()?,
+        Value::from(1)
+    );
+    drop(f);
+    let written = fs::read_to_string(test_file).expect("failed to read dry_run.json");
+    assert_eq!(written, original);
+
+    // direct writes and deletions are previewed as well
+    Program::parse(
+        "./src/tests/data/dry_run.json^fs[w] = 'x'\n\
+         delete ./src/tests/data^fs[w]/dry_run.json",
+    )?
+    .run(params)?;
+    let written = fs::read_to_string(test_file).expect("failed to read dry_run.json");
+    assert_eq!(written, original);
+
+    transaction::begin(true)?;
+    Xell::new("./src/tests/data/dry_run.json^fs[w]")
+        .write()
+        .value("x")?;
+    Xell::new("./src/tests/data^fs[w]/dry_run.json")
+        .write()
+        .detach()?;
+    let changes = transaction::finish()?;
+    assert_eq!(changes.writes.len(), 1);
+    assert_eq!(changes.writes[0].serial, "x");
+    assert_eq!(changes.removals.len(), 1);
+    assert!(changes.removals[0].path.ends_with("dry_run.json"));
+    drop(changes);
+    let written = fs::read_to_string(test_file).expect("failed to read dry_run.json");
+    assert_eq!(written, original);
+
+    fs::remove_file(test_file).expect("failed to cleanup dry_run.json");
+    Ok(())
+}
+
+#[test]
+fn program_transaction() -> Res<()> {
+    let file_a = "./src/tests/data/transaction_a.json";
+    let file_b = "./src/tests/data/transaction_b.json";
+    fs::write(file_a, r#"{"x":1}"#).expect("failed to seed transaction_a.json");
+    fs::write(file_b, r#"{"y":1}"#).expect("failed to seed transaction_b.json");
+
+    // a failing statement leaves all files untouched
+    let err = Program::parse(
+        "$a := ./src/tests/data/transaction_a.json^fs[w]^json\n\
+         $b := ./src/tests/data/transaction_b.json^fs[w]^json\n\
+         $a/x = 2\n\
+         $b/y = 1 / 0",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected division error");
+    assert!(format!("{}", err).contains("division by zero"));
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":1}"#);
+
+    // all files are written at the end
+    Program::parse(
+        "./src/tests/data/transaction_a.json^fs[w]^json/x = 2\n\
+         ./src/tests/data/transaction_b.json^fs[w]^json/y = 2",
+    )?
+    .run(ProgramParams::default())?;
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":2}"#);
+    assert_eq!(fs::read_to_string(file_b).unwrap(), r#"{"y":2}"#);
+
+    // direct writes to files are also written only if all statements succeed
+    let file_c = "./src/tests/data/transaction_c.txt";
+    fs::write(file_c, "c").expect("failed to seed transaction_c.txt");
+    let err = Program::parse(
+        "./src/tests/data/transaction_c.txt^fs[w] = 'new'\n\
+         ./src/tests/data/transaction_a.json^fs[w]^json/x = 1 / 0",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected division error");
+    assert!(format!("{}", err).contains("division by zero"));
+    assert_eq!(fs::read_to_string(file_c).unwrap(), "c");
+
+    // and in the order of the statements, with the elevations
+    Program::parse(
+        "./src/tests/data/transaction_a.json^fs[w]^json/x = 5\n\
+         ./src/tests/data/transaction_a.json^fs[w] = '{\"x\":9}'",
+    )?
+    .run(ProgramParams::default())?;
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":9}"#);
+    Program::parse(
+        "./src/tests/data/transaction_a.json^fs[w] = '{\"x\":6}'\n\
+         ./src/tests/data/transaction_a.json^fs[w]^json/x += 1",
+    )?
+    .run(ProgramParams::default())?;
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":7}"#);
+
+    // deleted files are removed only if all statements succeed
+    let err = Program::parse(
+        "delete ./src/tests/data^fs[w]/transaction_c.txt\n\
+         ./src/tests/data/transaction_a.json^fs[w]^json/x = 1 / 0",
+    )?
+    .run(ProgramParams::default())
+    .expect_err("expected division error");
+    assert!(format!("{}", err).contains("division by zero"));
+    assert!(fs::exists(file_c).unwrap());
+    Program::parse("delete ./src/tests/data^fs[w]/transaction_c.txt")?
+        .run(ProgramParams::default())?;
+    assert!(!fs::exists(file_c).unwrap());
+
+    // the elevations of the same file share their changes
+    Program::parse(
+        "./src/tests/data/transaction_a.json^fs[w]^json/x = 3\n\
+         ./src/tests/data/transaction_a.json^fs[w]^json/x += 1",
+    )?
+    .run(ProgramParams::default())?;
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":4}"#);
+
+    // the variables are written back on drop again after the program
+    let mut ctx = ExecutionContext::default();
+    Program::parse("$a := ./src/tests/data/transaction_a.json^fs[w]^json; $a/x = 2")?
+        .run_in_context(ProgramParams::default(), &mut ctx)?;
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":2}"#);
+    ctx.var("a").unwrap().to("/x").write().value(3)?;
+    drop(ctx);
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":3}"#);
+    fs::write(file_a, r#"{"x":2}"#).expect("failed to seed transaction_a.json");
+
+    // a failed commit restores the files already written
+    transaction::begin(false)?;
+    let a = Xell::new("./src/tests/data/transaction_a.json^fs[w]^json");
+    let b = Xell::new("./src/tests/data/transaction_b.json^fs[w]^json");
+    a.to("/x").write().value(5)?;
+    b.to("/y").write().value(5)?;
+    let changes = transaction::finish()?;
+    assert_eq!(changes.writes.len(), 2);
+    fs::remove_file(file_b).expect("failed to remove transaction_b.json");
+    fs::create_dir(file_b).expect("failed to create transaction_b.json dir");
+    transaction::commit(&changes).expect_err("expected commit error");
+    assert_eq!(fs::read_to_string(file_a).unwrap(), r#"{"x":2}"#);
+
+    drop((a, b, changes));
+    fs::remove_file(file_a).expect("failed to cleanup transaction_a.json");
+    fs::remove_dir(file_b).expect("failed to cleanup transaction_b.json");
+    Ok(())
+}
+
+#[test]
+fn program_match_limit_and_count() -> Res<()> {
+    let test_file = "./src/tests/data/match_limit.json";
+    fs::write(test_file, "[1, 2, 3, 4]").expect("failed to seed match_limit.json");
+    let program = Program::parse(
+        "$v := ./src/tests/data/match_limit.json^json\n\
+         $v/*\n\
+         $v/*[#value>2]\n\
+         $v/*[#value>9]",
+    )?;
+
+    let mut ctx = ExecutionContext::default();
+    program.run_in_context(ProgramParams::default(), &mut ctx)?;
+    assert_eq!((ctx.queries(), ctx.matches()), (3, 6));
+
+    let params = ProgramParams {
+        match_limit: Some(1),
+        count: true,
+        ..ProgramParams::default()
+    };
+    let mut ctx = ExecutionContext::default();
+    program.run_in_context(params, &mut ctx)?;
+    assert_eq!((ctx.queries(), ctx.matches()), (3, 2));
+
+    fs::remove_file(test_file).expect("failed to cleanup match_limit.json");
+    Ok(())
+}
+
+#[test]
+fn program_comments() -> Res<()> {
+    let text = "\n# bump the api\n$f := ./a.json^json  # the config\n\n  \n\
+                $f/*[ #value == 1 ]#label\n$f/x = \"a # b\" #\n";
+    let stripped = Program::strip_comments(text);
+    assert_eq!(stripped.chars().count(), text.chars().count());
+    assert_eq!(
+        stripped.lines().map(str::trim_end).collect::<Vec<_>>(),
+        vec![
+            "",
+            "",
+            "$f := ./a.json^json",
+            "",
+            "",
+            "$f/*[ #value == 1 ]#label",
+            "$f/x = \"a # b\"",
+        ]
+    );
+
+    let program = Program::parse(&stripped)?;
+    assert_eq!(program.0.len(), 3);
+    assert!(matches!(program.0[1], Statement::Path(..)));
+    assert_eq!(program.0[2].to_string(), "$f/x = \"a # b\"");
+
+    // error positions refer to the lines of the original text
+    let err = Program::parse(&Program::strip_comments("# comment\n\n$f/x]"))
+        .expect_err("expected parse error");
+    assert!(err.data.msg.contains("<program>:3:"), "{}", err.data.msg);
+    Ok(())
+}
+
+#[test]
+fn program_delete() -> Res<()> {
+    let prog = Program::parse("delete  ./a.json^json/x")?;
+    assert_eq!(prog.to_string(), "delete ./a.json^json/x\n");
+
+    let test_file = "./src/tests/data/delete.json";
+    fs::write(test_file, r#"{"a": [1, 2, 3, 4], "b": {"c": 1}, "d": 2}"#)
+        .expect("failed to seed delete.json");
+    Program::parse(
+        "$f := ./src/tests/data/delete.json^fs[w]^json\n\
+         delete $f/a/*[#value>=2]\n\
+         delete $f/b/c",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read delete.json");
+    assert_eq!(written, r#"{"a":[1],"b":{},"d":2}"#);
+
+    fs::remove_file(test_file).expect("failed to cleanup delete.json");
+    Ok(())
+}
+
+#[test]
+fn program_create() -> Res<()> {
+    let prog = Program::parse("create  ./a.json^json/x/[0] = 1")?;
+    assert_eq!(prog.to_string(), "create ./a.json^json/x/[0] = 1\n");
+
+    let test_file = "./src/tests/data/create.json";
+    fs::write(test_file, r#"{"auths": {}}"#).expect("failed to seed create.json");
+
+    // a plain assignment does not create the missing path
+    Program::parse("./src/tests/data/create.json^fs[w]^json/auths/docker.io/username = ignored")?
+        .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read create.json");
+    assert_eq!(written, r#"{"auths": {}}"#);
+
+    Program::parse(
+        "$f := ./src/tests/data/create.json^fs[w]^json\n\
+         create $f/auths/docker.io/username = newuser\n\
+         create $f/auths/docker.io/username = user\n\
+         create $f/mirrors/[0]/url = 'https://mirror'\n\
+         create $f/mirrors/[1] = none",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read create.json");
+    assert_eq!(
+        written,
+        r#"{"auths":{"docker.io":{"username":"user"}},"mirrors":[{"url":"https://mirror"},"none"]}"#
+    );
+
+    // only the next index of a group can be created
+    let err = Program::parse("create ./src/tests/data/create.json^fs[w]^json/mirrors/[3] = 1")?
+        .run(ProgramParams::default())
+        .expect_err("expected create error");
+    assert!(format!("{}", err).contains("only [2] can be appended"));
+
+    fs::remove_file(test_file).expect("failed to cleanup create.json");
+    Ok(())
+}
+
+#[test]
+fn program_structured_assignment() -> Res<()> {
+    let prog = Program::parse(
+        "$svc/ports = [ \"80:80\", '443:443' ]\n\
+         $cfg/limits = {cpu: 2, \"mem\": \"1Gi\", ratio: 0.5, on: true, off: null, l: [], o: {\n}}",
+    )?;
+    assert_eq!(
+        prog.to_string(),
+        "$svc/ports = [\"80:80\", \"443:443\"]\n\
+         $cfg/limits = {\"cpu\": 2, \"mem\": \"1Gi\", \"ratio\": 0.5, \"on\": true, \"off\": null, \"l\": [], \"o\": {}}\n"
+    );
+    assert_eq!(
+        Program::parse(&prog.to_string())?.to_string(),
+        prog.to_string()
+    );
+
+    let test_file = "./src/tests/data/structured_assignment.json";
+    fs::write(
+        test_file,
+        r#"{"ports": 80, "limits": {"cpu": 1, "gpu": 1}, "x": 0}"#,
+    )
+    .expect("failed to seed structured_assignment.json");
+    Program::parse(
+        "$f := ./src/tests/data/structured_assignment.json^fs[w]^json\n\
+         $f/ports = [80, {host: 8080}]\n\
+         $f/limits = {cpu: 2, mem: \"1Gi\"}\n\
+         $f/x = 1.5",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read structured_assignment.json");
+    assert_eq!(
+        written,
+        r#"{"ports":[80,{"host":8080}],"limits":{"cpu":2,"mem":"1Gi"},"x":1.5}"#
+    );
+    fs::remove_file(test_file).expect("failed to cleanup structured_assignment.json");
+
+    let test_file = "./src/tests/data/structured_assignment.toml";
+    fs::write(test_file, "a = 1\n").expect("failed to seed structured_assignment.toml");
+    Program::parse("./src/tests/data/structured_assignment.toml^fs[w]^toml/a = {b: [1, 2]}")?
+        .run(ProgramParams::default())?;
+    let toml = Xell::new("./src/tests/data/structured_assignment.toml^toml").err()?;
+    assert_eq!(toml.to("/a/b/[1]").read().value()?, Value::from(2));
+    fs::remove_file(test_file).expect("failed to cleanup structured_assignment.toml");
+    Ok(())
+}
+
+#[test]
+fn program_path_assignment() -> Res<()> {
+    let prog = Program::parse("$dst/db = $src/db/[0]\n$dst/name = dev; $dst/x = ./dev")?;
+    assert_eq!(
+        prog.to_string(),
+        "$dst/db = $src/db/[0]\n$dst/name = \"dev\"\n$dst/x = ./dev\n"
+    );
+
+    let src_file = "./src/tests/data/path_assignment.json";
+    let dst_file = "./src/tests/data/path_assignment.yaml";
+    fs::write(
+        src_file,
+        r#"{"db": {"host": "db.local", "ports": [5432, 5433], "opts": {}}, "name": "staging"}"#,
+    )
+    .expect("failed to seed path_assignment.json");
+    fs::write(dst_file, "db: none\nname: dev\n").expect("failed to seed path_assignment.yaml");
+    Program::parse(
+        "$src := ./src/tests/data/path_assignment.json^json\n\
+         $dst := ./src/tests/data/path_assignment.yaml^fs[w]^yaml\n\
+         $dst/db = $src/db\n\
+         $dst/name = $src/name",
+    )?
+    .run(ProgramParams::default())?;
+    let yaml = Xell::new("./src/tests/data/path_assignment.yaml^yaml").err()?;
+    assert_eq!(yaml.to("/db/host").read().value()?, Value::from("db.local"));
+    assert_eq!(yaml.to("/db/ports/[1]").read().value()?, Value::from(5433));
+    assert_eq!(yaml.to("/db/opts").read().ty()?, "object");
+    assert_eq!(yaml.to("/name").read().value()?, Value::from("staging"));
+
+    let err = Program::parse(
+        "$src := ./src/tests/data/path_assignment.json^json\n\
+         $dst := ./src/tests/data/path_assignment.yaml^fs[w]^yaml\n\
+         $dst/name = $src/db/ports/*",
+    )?
+    .run(ProgramParams::default())
+    .unwrap_err();
+    assert!(
+        err.data.msg.contains("matches more than one cell"),
+        "{:?}",
+        err
+    );
+    let err = Program::parse(
+        "$src := ./src/tests/data/path_assignment.json^json\n\
+         $dst := ./src/tests/data/path_assignment.yaml^fs[w]^yaml\n\
+         $dst/name = $src/missing",
+    )?
+    .run(ProgramParams::default())
+    .unwrap_err();
+    assert_eq!(err.kind, HErrKind::None);
+
+    // attributes cannot be kept in a value
+    let xml_file = "./src/tests/data/path_assignment.xml";
+    fs::write(xml_file, r#"<db host="db.local"><port>5432</port></db>"#)
+        .expect("failed to seed path_assignment.xml");
+    let err = Program::parse(
+        "$dst := ./src/tests/data/path_assignment.yaml^fs[w]^yaml\n\
+         $dst/name = ./src/tests/data/path_assignment.xml^xml/db",
+    )?
+    .run(ProgramParams::default())
+    .unwrap_err();
+    assert!(err.data.msg.contains("has attributes"), "{:?}", err);
+
+    fs::remove_file(xml_file).expect("failed to cleanup path_assignment.xml");
+    fs::remove_file(src_file).expect("failed to cleanup path_assignment.json");
+    fs::remove_file(dst_file).expect("failed to cleanup path_assignment.yaml");
+    Ok(())
+}
+
+#[test]
+fn program_compound_assignment() -> Res<()> {
+    let prog = Program::parse(
+        "$f/n += 1\n$f/a = $g/x * (2 + 1) - 1; $f/b = 1 - (2 - 3)\n$f/c = (1 - 2) - 3\n$f/s = \"v\" + $g/x",
+    )?;
+    assert_eq!(
+        prog.to_string(),
+        "$f/n += 1\n$f/a = $g/x * (2 + 1) - 1\n$f/b = 1 - (2 - 3)\n$f/c = 1 - 2 - 3\n$f/s = \"v\" + $g/x\n"
+    );
+    assert_eq!(
+        Program::parse(&prog.to_string())?.to_string(),
+        prog.to_string()
+    );
+
+    let test_file = "./src/tests/data/compound_assignment.json";
+    fs::write(
+        test_file,
+        r#"{"replicas": 2, "ratio": 0.5, "name": "api", "ports": [80, 443], "x": 0, "y": 0}"#,
+    )
+    .expect("failed to seed compound_assignment.json");
+    Program::parse(
+        "$f := ./src/tests/data/compound_assignment.json^fs[w]^json\n\
+         $f/replicas += 1\n\
+         $f/ratio *= 2\n\
+         $f/name = $f/name + \"-v2\"\n\
+         $f/ports/* -= 80 - 1000\n\
+         $f/x = 7 / 2\n\
+         $f/y = $f/replicas * 2 - 1",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read compound_assignment.json");
+    assert_eq!(
+        written,
+        r#"{"replicas":3,"ratio":1.0,"name":"api-v2","ports":[1000,1363],"x":3.5,"y":5}"#
+    );
+
+    // operators need whitespace around them, otherwise `/` and `-` belong to
+    // paths and labels
+    let prog = Program::parse("$f/a = $f/n/2\n$f/b = $f/n / 2\n$f/c = 2024-01-01")?;
+    assert_eq!(
+        prog.to_string(),
+        "$f/a = $f/n/2\n$f/b = $f/n / 2\n$f/c = \"2024-01-01\"\n"
+    );
+    Program::parse("$f/a = $f/n -1").expect_err("expected parse error");
+    Program::parse(
+        "$f := ./src/tests/data/compound_assignment.json^fs[w]^json\n\
+         $f/replicas = $f/replicas / 3\n\
+         $f/name = 2024-01-01",
+    )?
+    .run(ProgramParams::default())?;
+    let written = fs::read_to_string(test_file).expect("failed to read compound_assignment.json");
+    assert_eq!(
+        written,
+        r#"{"replicas":1,"ratio":1.0,"name":"2024-01-01","ports":[1000,1363],"x":3.5,"y":5}"#
+    );
+
+    let run = |program: &str| {
+        Program::parse(&format!(
+            "$f := ./src/tests/data/compound_assignment.json^json\n{}",
+            program
+        ))?
+        .run(ProgramParams::default())
+    };
+    let err = run("$f/name += 1").unwrap_err();
+    assert_eq!(
+        err.data.msg,
+        "cannot apply `+` to string `\"2024-01-01\"` and int `1`"
+    );
+    let err = run("$f/replicas -= \"1\"").unwrap_err();
+    assert_eq!(
+        err.data.msg,
+        "cannot apply `-` to int `1` and string `\"1\"`"
+    );
+    let err = run("$f/x = $f/ports - 1").unwrap_err();
+    assert_eq!(
+        err.data.msg,
+        "cannot apply `-` to array `[1000, 1363]` and int `1`"
+    );
+    let err = run("$f/replicas /= 0").unwrap_err();
+    assert_eq!(err.data.msg, "division by zero: `1`");
+
+    fs::remove_file(test_file).expect("failed to cleanup compound_assignment.json");
+    Ok(())
+}
+
+#[test]
+fn program_for_and_if() -> Res<()> {
+    let prog = Program::parse(
+        "for $svc in ./compose.yaml^yaml/services/* {\n\
+             if $svc/latest { $svc/pull = always }\n\
+             else if $svc/build {\n\
+                 $svc/pull = never\n\
+             } else {}\n\
+         }\n\
+         if ./x.json^json/a {} else { delete ./x.json^json/b; ./x.json^json/c = 1 }",
+    )?;
+    assert_eq!(
+        prog.to_string(),
+        "for $svc in ./compose.yaml^yaml/services/* {\n\
+         \x20   if $svc/latest {\n\
+         \x20       $svc/pull = \"always\"\n\
+         \x20   } else if $svc/build {\n\
+         \x20       $svc/pull = \"never\"\n\
+         \x20   }\n\
+         }\n\
+         if ./x.json^json/a {} else {\n\
+         \x20   delete ./x.json^json/b\n\
+         \x20   ./x.json^json/c = 1\n\
+         }\n"
+    );
+    assert_eq!(
+        Program::parse(&prog.to_string())?.to_string(),
+        prog.to_string()
+    );
+
+    let test_file = "./src/tests/data/for_and_if.yaml";
+    fs::write(
+        test_file,
+        "services:\n  api:\n    image: api:latest\n  db:\n    image: postgres:16\n    build: .\n  web:\n    image: nginx:1\n",
+    )
+    .expect("failed to seed for_and_if.yaml");
+    Program::parse(
+        "$f := ./src/tests/data/for_and_if.yaml^fs[w]^yaml\n\
+         $svc := $f/services/web\n\
+         for $svc in $f/services/* {\n\
+             $image := $svc/image\n\
+             if $svc/image[#value endswith ':latest'] {\n\
+                 create $svc/pull = always\n\
+             } else if $svc/build {\n\
+                 create $svc/pull = never\n\
+             } else {\n\
+                 create $svc/pull = missing\n\
+             }\n\
+         }\n\
+         create $svc/checked = true\n\
+         if ./src/tests/data/missing.json^fs^json/a { create $f/missing = true }\n\
+         if $f/services/nothing { create $f/nothing = true } else { create $f/nothing = false }",
+    )?
+    .run(ProgramParams::default())?;
+    let yaml = Xell::new("./src/tests/data/for_and_if.yaml^yaml").err()?;
+    assert_eq!(
+        yaml.to("/services/api/pull").read().value()?,
+        Value::from("always")
+    );
+    assert_eq!(
+        yaml.to("/services/db/pull").read().value()?,
+        Value::from("never")
+    );
+    assert_eq!(
+        yaml.to("/services/web/pull").read().value()?,
+        Value::from("missing")
+    );
+    // the loop variable shadows the outer binding only inside the loop
+    assert_eq!(
+        yaml.to("/services/web/checked").read().value()?,
+        Value::Bool(true)
+    );
+    assert!(yaml.to("/services/db/checked").read().value().is_err());
+    assert!(yaml.to("/missing").read().value().is_err());
+    assert_eq!(yaml.to("/nothing").read().value()?, Value::Bool(false));
+
+    // variables bound inside a block are not visible after it
+    let err = Program::parse(
+        "for $svc in ./src/tests/data/for_and_if.yaml^yaml/services/* { $image := $svc/image }\n\
+         $image",
+    )?
+    .run(ProgramParams::default())
+    .unwrap_err();
+    assert!(err.data.msg.contains("undefined variable"), "{:?}", err);
+
+    fs::remove_file(test_file).expect("failed to cleanup for_and_if.yaml");
+    Ok(())
+}
+
+#[test]
+fn program_functions() -> Res<()> {
+    let prog = Program::parse(
+        "fn images($compose) := $compose^yaml/services/*/image\n\
+         fn bump($svc, $by) { $svc/replicas += 1 }\n\
+         fn none() {}\n\
+         images(./compose.yaml)/[0]\n\
+         bump(images($c)/.., ./x.json^json/n); none()\n\
+         ./compose.yaml^yaml/services/*[is_latest(., /image, $c/x)]",
+    )?;
+    assert_eq!(
+        prog.to_string(),
+        "fn images($compose) := $compose^yaml/services/*/image\n\
+         fn bump($svc, $by) {\n\
+         \x20   $svc/replicas += 1\n\
+         }\n\
+         fn none() {}\n\
+         images(./compose.yaml)/[0]\n\
+         bump(images($c)/.., ./x.json^json/n)\n\
+         none()\n\
+         ./compose.yaml^yaml/services/*[is_latest(., /image, $c/x)]\n"
+    );
+    assert_eq!(
+        Program::parse(&prog.to_string())?.to_string(),
+        prog.to_string()
+    );
+
+    let test_file = "./src/tests/data/functions.yaml";
+    fs::write(
+        test_file,
+        "services:\n  api:\n    image: api:latest\n    replicas: 1\n  db:\n    image: postgres:16\n    replicas: 1\n",
+    )
+    .expect("failed to seed functions.yaml");
+    // functions defined in one program, like the prelude, can be called
+    // from the next programs which run in the same context
+    let mut ctx = ExecutionContext::default();
+    Program::parse(
+        "fn services($compose) := $compose^yaml/services\n\
+         fn is_latest($svc) := $svc/image[#value endswith ':latest']\n\
+         fn bump($svc) { $svc/replicas += 1 }",
+    )?
+    .run_in_context(ProgramParams::default(), &mut ctx)?;
+    Program::parse(
+        "for $svc in services(./src/tests/data/functions.yaml^fs[w])/*[is_latest(.)] {\n\
+             bump($svc)\n\
+         }\n\
+         $first := services(./src/tests/data/functions.yaml)/*/image",
+    )?
+    .run_in_context(ProgramParams::default(), &mut ctx)?;
+    let first = ctx.var("first").expect("first is bound");
+    assert_eq!(first.read().value()?, Value::from("api:latest"));
+    let yaml = Xell::new("./src/tests/data/functions.yaml^yaml").err()?;
+    assert_eq!(
+        yaml.to("/services/api/replicas").read().value()?,
+        Value::from(2)
+    );
+    assert_eq!(
+        yaml.to("/services/db/replicas").read().value()?,
+        Value::from(1)
+    );
+
+    let run = |program: &str| -> Res<()> {
+        let mut ctx = ctx.clone();
+        Program::parse(program)?.run_in_context(ProgramParams::default(), &mut ctx)
+    };
+    let err = run("missing(./x)").unwrap_err();
+    assert_eq!(err.data.msg, "undefined function missing()");
+    let err = run("services(./src, ./src)").unwrap_err();
+    assert_eq!(err.data.msg, "function services() takes 1 arguments, got 2");
+    let err = run("$x := bump(./src/tests/data/functions.yaml)").unwrap_err();
+    assert_eq!(
+        err.data.msg,
+        "bump() is a procedure, it can only be called as a statement"
+    );
+    let err = run("fn forever($x) := forever($x)/a\nforever(./src)").unwrap_err();
+    assert!(
+        err.data
+            .msg
+            .starts_with("too many nested calls of forever()")
+    );
+    let err = run("if ./src { fn local() := ./src }\nlocal()").unwrap_err();
+    assert_eq!(err.data.msg, "undefined function local()");
+
+    fs::remove_file(test_file).expect("failed to cleanup functions.yaml");
+    Ok(())
+}
@@ -1,4 +1,10 @@
-use crate::{api::*, config::ColorPalette, pprint, utils::log::set_verbose};
+use crate::{
+    api::*,
+    config::ColorPalette,
+    pprint,
+    prog::{ExecutionContext, Path, searcher::Searcher},
+    utils::log::set_verbose,
+};
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::time::Duration;
 
@@ -139,6 +145,100 @@ fn search_simple_search_with_index() -> Res<()> {
     Ok(())
 }
 
+#[test]
+fn search_index_ranges() -> Res<()> {
+    let root = Xell::from("[10, 11, 12, 13, 14, 15]").be("json");
+
+    let eval = str_eval(root.clone(), "/[2..5]")?;
+    assert_eq!(eval, [":12", ":13", ":14"]);
+
+    let eval = str_eval(root.clone(), "/*[..2]")?;
+    assert_eq!(eval, [":10", ":11"]);
+
+    let eval = str_eval(root.clone(), "/[-3..]")?;
+    assert_eq!(eval, [":13", ":14", ":15"]);
+
+    let eval = str_eval(root.clone(), "/[1..-1][#value>12]")?;
+    assert_eq!(eval, [":13", ":14"]);
+
+    let eval = str_eval(root.clone(), "/[4..100]")?;
+    assert_eq!(eval, [":14", ":15"]);
+
+    let eval = str_eval(root.clone(), "/[4..2]")?;
+    assert!(eval.is_empty());
+
+    const TREE: &str = r#"
+    <test>
+        <a>1</a>
+        <b>x</b>
+        <a>2</a>
+        <a>3</a>
+    </test>
+        "#;
+    let root = Xell::from(TREE).be("xml");
+
+    let eval = str_eval(root.clone(), "/test/a[1..]")?;
+    assert_eq!(eval.len(), 2);
+
+    let eval = str_eval(root.clone(), "/test/a[-2..-1]")?;
+    assert_eq!(eval, ["a:2"]);
+
+    Ok(())
+}
+
+#[test]
+fn search_label_alternatives() -> Res<()> {
+    const TREE: &str = r#"
+        services:
+            web:
+                ports: [80]
+                image: nginx
+                name: web-server
+                build: ./web
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), "/services/web/{name,image,ports}")?;
+    assert_eq!(eval, ["ports:", "image:nginx", "name:web-server"]);
+
+    let eval = str_eval(root.clone(), "/services/*/{ image , image, missing }")?;
+    assert_eq!(eval, ["image:nginx"]);
+
+    let eval = str_eval(root.clone(), "/services/web/{build,name}[-1]")?;
+    assert_eq!(eval, ["build:./web"]);
+
+    let root = Xell::from("<t><a>1</a><b>2</b><a>3</a><c>4</c></t>").be("xml");
+    let eval = str_eval(root.clone(), "/t/{a,b}")?;
+    assert_eq!(eval, ["a:1", "b:2", "a:3"]);
+
+    let eval = str_eval(root.clone(), "/t/a")?;
+    assert_eq!(eval, ["a:1", "a:3"]);
+
+    Ok(())
+}
+
+#[test]
+fn search_label_globs() -> Res<()> {
+    const TREE: &str = r#"
+        config_a: 1
+        config_bb: 2
+        other: 3
+        config_c: 4
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), "/config_?")?;
+    assert_eq!(eval, ["config_a:1", "config_c:4"]);
+
+    let eval = str_eval(root.clone(), "/*_[ab]*")?;
+    assert_eq!(eval, ["config_a:1", "config_bb:2"]);
+
+    let eval = str_eval(root.clone(), "/config*[-1]")?;
+    assert_eq!(eval, ["config_c:4"]);
+
+    Ok(())
+}
+
 #[test]
 fn search_kleene() -> Res<()> {
     const TREE: &str = r#"
@@ -453,8 +553,19 @@ fn search_double_kleene_with_filter() -> Res<()> {
 
     pprint(&root, 0, 0, ColorPalette::None);
     let eval = str_eval(root.clone(), "/dir1/**[/size]")?;
+    assert_eq!(eval, ["f1:", "dir2:", "f2:", "f3:"]);
+
+    // the filter of `**` is not tested on the starting cell
+    let eval = str_eval(root.clone(), "/dir1/**[#label=='dir1']")?;
+    assert!(eval.is_empty());
+
+    // `***` tests the filter on every cell on the way down
+    let eval = str_eval(root.clone(), "/dir1/***[/size]")?;
     assert_eq!(eval, ["f1:", "dir2:", "f2:"]);
 
+    let eval = str_eval(root.clone(), "/dir1/***[#label=='dir1']")?;
+    assert_eq!(eval, ["dir1:"]);
+
     pprint(&root, 0, 0, ColorPalette::None);
     let eval = str_eval(root.clone(), "/dir1/**/*[/size]")?;
     assert_eq!(eval, ["f1:", "dir2:", "f2:", "f3:"]);
@@ -462,6 +573,298 @@ fn search_double_kleene_with_filter() -> Res<()> {
     Ok(())
 }
 
+#[test]
+fn search_parent_and_ancestors() -> Res<()> {
+    const TREE: &str = r#"
+        services:
+            web:
+                image: nginx
+            db:
+                image: postgres
+            cache:
+                build: ./cache
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), "/services/*/image/..#label")?;
+    assert_eq!(eval, [":web", ":db"]);
+
+    let eval = str_eval(root.clone(), "/**/image[=='postgres']/../..#label")?;
+    assert_eq!(eval, [":services"]);
+
+    let eval = str_eval(root.clone(), "/services/web/image/..**[#label]#label")?;
+    assert_eq!(eval, [":web", ":services"]);
+
+    let eval = str_eval(root.clone(), "/services/*[/build]/..**[:object]#label")?;
+    assert_eq!(eval, [":services"]);
+
+    // the parent of the yaml root is the string it was interpreted from
+    let ancestors = root.to("/services/db").ancestors()?;
+    assert_eq!(ancestors.len(), 3);
+    assert_eq!(ancestors[2].interpretation(), "value");
+    let eval = root
+        .all("/services/db/..**^yaml/services/cache/build")?
+        .into_iter()
+        .map(|c| Ok(c.read().value()?.to_string()))
+        .collect::<Res<Vec<_>>>()?;
+    assert_eq!(eval, ["./cache"]);
+
+    Ok(())
+}
+
+#[test]
+fn search_captures() -> Res<()> {
+    const TREE: &str = r#"
+        services:
+            web:
+                image: nginx
+                ports: [80, 443]
+            db:
+                image: postgres
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let mut searcher = Path::parse("/services/*[as svc]/ports/*")?.eval(root.clone());
+    let mut found = vec![];
+    while let Some(res) = searcher.next_with_captures() {
+        let (cell, captures) = res?;
+        let captures = captures
+            .iter()
+            .map(|(name, cell)| Ok(format!("{}={}", name, cell.read().label()?)))
+            .collect::<Res<Vec<_>>>()?;
+        found.push((cell.read().value()?.to_string(), captures));
+    }
+    assert_eq!(
+        found,
+        [
+            ("80".to_string(), vec!["svc=web".to_string()]),
+            ("443".to_string(), vec!["svc=web".to_string()]),
+        ]
+    );
+
+    // captures under kleene stars bind the cell matched by the capturing item
+    let mut searcher = Path::parse("/**/image[as img]")?.eval(root);
+    let mut found = vec![];
+    while let Some(res) = searcher.next_with_captures() {
+        let (cell, captures) = res?;
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].0, "img");
+        assert_eq!(captures[0].1.read().value()?, cell.read().value()?);
+        found.push(cell.read().value()?.to_string());
+    }
+    assert_eq!(found, ["nginx", "postgres"]);
+    Ok(())
+}
+
+#[test]
+fn search_operand_paths() -> Res<()> {
+    const TREE: &str = r#"
+        release:
+            image: nginx:2
+        users:
+            - id: ann
+            - id: bob
+        deployments:
+            a:
+                image: nginx:2
+                owner: eve
+                tag: x
+                default: x
+            b:
+                image: nginx:1
+                owner: bob
+                tag: y
+                default: z
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), "/deployments/*[/tag == /default]#label")?;
+    assert_eq!(eval, [":a"]);
+    let eval = str_eval(root.clone(), "/deployments/*[/tag != /default]#label")?;
+    assert_eq!(eval, [":b"]);
+
+    let mut ctx = ExecutionContext::default();
+    ctx.set_var("release", root.to("/release"));
+    ctx.set_var("cfg", root.clone());
+    let eval_in_ctx = |path: &str| -> Res<Vec<String>> {
+        Searcher::new_in_context(root.clone(), Path::parse(path)?, &ctx)
+            .map(|cell| Ok(cell?.read().label()?.to_string()))
+            .collect()
+    };
+    assert_eq!(
+        eval_in_ctx("/deployments/*[/image == $release/image]")?,
+        ["a"]
+    );
+    // the comparison is true if any value on the right side matches
+    assert_eq!(
+        eval_in_ctx("/deployments/*[/owner == $cfg/users/*/id]")?,
+        ["b"]
+    );
+    assert_eq!(
+        eval_in_ctx("/deployments/*[!(/owner == $cfg/users/*/id)]")?,
+        ["a"]
+    );
+    Ok(())
+}
+
+#[test]
+fn search_filter_ordering() -> Res<()> {
+    const TREE: &str = r#"
+        a: 1
+        b: 10
+        c: 2.5
+        d: "10"
+        e: abc
+        f: abd
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), "/*[#value>2]")?;
+    assert_eq!(eval, ["b:10", "c:2.5", "d:10"]);
+
+    let eval = str_eval(root.clone(), "/*[#value<=2]")?;
+    assert_eq!(eval, ["a:1"]);
+
+    let eval = str_eval(root.clone(), "/*[#value>=10]")?;
+    assert_eq!(eval, ["b:10", "d:10"]);
+
+    let eval = str_eval(root.clone(), "/*[#value<'abd']#label")?;
+    assert_eq!(eval, [":d", ":e"]);
+
+    // strings compare lexicographically with strings, numerically with numbers
+    let eval = str_eval(root.clone(), "/*[#value>'9']#label")?;
+    assert_eq!(eval, [":b", ":e", ":f"]);
+
+    // equality uses the same coercion as ordering
+    let eval = str_eval(root.clone(), "/*[#value==10]#label")?;
+    assert_eq!(eval, [":b", ":d"]);
+
+    let eval = str_eval(root.clone(), "/*[#value!='10']#label")?;
+    assert_eq!(eval, [":a", ":c", ":e", ":f"]);
+
+    Ok(())
+}
+
+#[test]
+fn search_filter_string_predicates() -> Res<()> {
+    const TREE: &str = r#"
+        main.rs: 1
+        search_test.rs: 2
+        path_test.rs: 3
+        services:
+            web:
+                image: nginx:latest
+            db:
+                image: postgres:15
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), r"/*[#label~='.*_test\.rs']#label")?;
+    assert_eq!(eval, [":search_test.rs", ":path_test.rs"]);
+
+    let eval = str_eval(root.clone(), "/*[#label startswith 'path']#label")?;
+    assert_eq!(eval, [":path_test.rs"]);
+
+    let eval = str_eval(root.clone(), "/*[#label endswith '.rs'][#value>=2]#label")?;
+    assert_eq!(eval, [":search_test.rs", ":path_test.rs"]);
+
+    let eval = str_eval(root.clone(), "/services/*[/image contains ':latest']#label")?;
+    assert_eq!(eval, [":web"]);
+
+    let eval = str_eval(root.clone(), "/services/*/image[~='^postgres:[0-9]+$']")?;
+    assert_eq!(eval, ["image:postgres:15"]);
+
+    let eval = str_eval(root.clone(), "/services/*/image[contains 'nginx']")?;
+    assert_eq!(eval, ["image:nginx:latest"]);
+
+    // a word operator must end at a word boundary
+    assert!(Path::parse("/*[#label containsx 'a']").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn search_filter_and_not() -> Res<()> {
+    const TREE: &str = r#"
+        services:
+            web:
+                image: registry.local/web:1
+            db:
+                image: postgres:15
+            cache:
+                build: ./cache
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(
+        root.clone(),
+        "/services/*[/image & !/image startswith 'registry.local/']#label",
+    )?;
+    assert_eq!(eval, [":db"]);
+
+    let eval = str_eval(root.clone(), "/services/*[!/image]#label")?;
+    assert_eq!(eval, [":cache"]);
+
+    let eval = str_eval(
+        root.clone(),
+        "/services/*[(/build | /image contains 'postgres') & !#label=='cache']#label",
+    )?;
+    assert_eq!(eval, [":db"]);
+
+    // `&` binds tighter than `|`
+    let eval = str_eval(
+        root.clone(),
+        "/services/*[/build | /image contains 'postgres' & #label=='web']#label",
+    )?;
+    assert_eq!(eval, [":cache"]);
+
+    Ok(())
+}
+
+#[test]
+fn search_filter_functions() -> Res<()> {
+    const TREE: &str = r#"
+        services:
+            web:
+                image: nginx
+                ports: [80, 443]
+            db:
+                image: postgres
+                ports: [5432]
+            cache:
+                image: redis-server
+                ports: []
+        "#;
+    let root = Xell::from(TREE).be("yaml");
+
+    let eval = str_eval(root.clone(), "/services/*[count(/ports/*)==0]#label")?;
+    assert_eq!(eval, [":cache"]);
+
+    let eval = str_eval(root.clone(), "/services/*[count(/ports/*)>=1]#label")?;
+    assert_eq!(eval, [":web", ":db"]);
+
+    let eval = str_eval(root.clone(), "/services/*[len(/image)>6]#label")?;
+    assert_eq!(eval, [":db", ":cache"]);
+
+    let eval = str_eval(root.clone(), "/services/*[sum(/ports/*)==523]#label")?;
+    assert_eq!(eval, [":web"]);
+
+    let eval = str_eval(root.clone(), "/services/*[max(/ports/*)>1000]#label")?;
+    assert_eq!(eval, [":db"]);
+
+    let eval = str_eval(root.clone(), "/services/*[min(/ports/*)<100]#label")?;
+    assert_eq!(eval, [":web"]);
+
+    let eval = str_eval(root.clone(), "/services/*[avg(/ports/*)<1000]#label")?;
+    assert_eq!(eval, [":web"]);
+
+    // without a comparison, a function is true if its result is not empty or zero
+    let eval = str_eval(root.clone(), "/services/*[!count(/ports/*)]#label")?;
+    assert_eq!(eval, [":cache"]);
+
+    Ok(())
+}
+
 pub fn str_eval(root: Xell, path: &str) -> Res<Vec<String>> {
     root.all(path)?
         .into_iter()
@@ -73,3 +73,15 @@ fn text_empty_document_has_no_lines() -> Res<()> {
     assert_eq!(text.read().serial()?, "");
     Ok(())
 }
+
+#[test]
+fn text_delete_line() -> Res<()> {
+    let raw = Xell::from("one\ntwo\nthree\n").policy(WritePolicy::NoAutoWrite);
+    let text = raw.be("text");
+    text.to("/[1]").write().detach()?;
+
+    text.save(&text.origin())?;
+    assert_eq!(raw.read().value()?, "one\nthree\n");
+    assert!(text.write().detach().is_err());
+    Ok(())
+}
@@ -61,3 +61,32 @@ fn toml_write_and_save() -> Res<()> {
 
     Ok(())
 }
+
+#[test]
+fn toml_delete() -> Res<()> {
+    let data = Xell::from("[number]\nx = 1\ny = 2").policy(WritePolicy::NoAutoWrite);
+    let toml = data.be("toml");
+    toml.to("/number/x").write().detach()?;
+
+    toml.save(&toml.origin())?;
+    assert_eq!(data.to("^toml/number").sub().len()?, 1);
+    assert_eq!(data.to("^toml/number/y").read().value()?, Value::from(2));
+    Ok(())
+}
+
+#[test]
+fn toml_create_and_add() -> Res<()> {
+    let data = Xell::from("a = 1").policy(WritePolicy::NoAutoWrite);
+    let toml = data.be("toml");
+    let root = toml.sub().err()?;
+    root.add(None, root.create(Some("server".into()), None)?)?;
+    let server = toml.to("/server").sub().err()?;
+    server.add(None, server.create(Some("port".into()), Some(80.into()))?)?;
+
+    toml.save(&toml.origin())?;
+    assert_eq!(
+        data.to("^toml/server/port").read().value()?,
+        Value::from(80)
+    );
+    Ok(())
+}
@@ -111,3 +111,37 @@ fn xml_write_and_save() -> Res<()> {
 
     Ok(())
 }
+
+#[test]
+fn xml_delete() -> Res<()> {
+    let text =
+        Xell::from(r#"<doc a="1" b="2"><x>1</x><y>2</y></doc>"#).policy(WritePolicy::NoAutoWrite);
+    let xml = text.be("xml");
+    xml.to("/doc/x").write().detach()?;
+    xml.to("/doc@a").write().detach()?;
+
+    xml.save(&xml.origin())?;
+    assert_eq!(text.read().value()?, r#"<doc b="2"><y>2</y></doc>"#);
+    Ok(())
+}
+
+#[test]
+fn xml_create_and_add() -> Res<()> {
+    let text = Xell::from(r#"<doc><a/></doc>"#).policy(WritePolicy::NoAutoWrite);
+    let xml = text.be("xml");
+    let doc = xml.to("/doc").sub().err()?;
+    doc.add(Some(0), doc.create(Some("first".into()), Some("1".into()))?)?;
+    let attrs = xml.to("/doc/a").attr().err()?;
+    attrs.add(None, attrs.create(Some("id".into()), Some(2.into()))?)?;
+    assert!(
+        doc.add(None, attrs.create(Some("x".into()), None)?)
+            .is_err()
+    );
+
+    xml.save(&xml.origin())?;
+    assert_eq!(
+        text.read().value()?,
+        r#"<doc><first>1</first><a id="2"/></doc>"#
+    );
+    Ok(())
+}
@@ -75,3 +75,32 @@ fn yaml_write_and_save() -> Res<()> {
 
     Ok(())
 }
+
+#[test]
+fn yaml_delete() -> Res<()> {
+    let text = Xell::from("a: 1\nb:\n- x\n- y\n").policy(WritePolicy::NoAutoWrite);
+    let yaml = text.be("yaml");
+    yaml.to("/a").write().detach()?;
+    yaml.to("/b/[0]").write().detach()?;
+
+    yaml.save(&yaml.origin())?;
+    assert_eq!(text.to("^yaml/b/[0]").read().value()?, "y");
+    assert!(text.to("^yaml/a").err().is_err());
+    Ok(())
+}
+
+#[test]
+fn yaml_create_and_add() -> Res<()> {
+    let text = Xell::from("a: 1\n").policy(WritePolicy::NoAutoWrite);
+    let yaml = text.be("yaml");
+    let root = yaml.sub().err()?;
+    let list = root.create(Some("b".into()), None)?;
+    list.write().ty("array")?;
+    root.add(None, list)?;
+    let list = yaml.to("/b").sub().err()?;
+    list.add(None, list.create(None, Some("x".into()))?)?;
+
+    yaml.save(&yaml.origin())?;
+    assert_eq!(text.to("^yaml/b/[0]").read().value()?, "x");
+    Ok(())
+}
@@ -1,15 +1,76 @@
-use std::process::Command;
+use std::process::{Command, Output};
 
-#[test]
-fn cli_exits_successfully() {
-    let temp_home = std::env::temp_dir().join(format!("hial-cli-exit-home-{}", std::process::id()));
+fn run_cli(name: &str, args: &[&str]) -> Output {
+    let temp_home = std::env::temp_dir().join(format!(
+        "hial-cli-exit-home-{}-{}",
+        name,
+        std::process::id()
+    ));
     std::fs::create_dir_all(&temp_home).expect("failed to create temp home");
-    let status = Command::new(env!("CARGO_BIN_EXE_hiallib"))
-        .arg("src/tests/data/assignment.json^json/a")
+    let output = Command::new(env!("CARGO_BIN_EXE_hiallib"))
+        .args(args)
         .env("HOME", &temp_home)
-        .status()
+        .output()
         .expect("failed to run CLI binary");
-
-    assert!(status.success(), "CLI exited with status: {status}");
     let _ = std::fs::remove_dir_all(&temp_home);
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn cli_exits_successfully() {
+    let output = run_cli("success", &["src/tests/data/assignment.json^json/a"]);
+    assert_eq!(output.status.code(), Some(0), "CLI exited with: {output:?}");
+}
+
+#[test]
+fn cli_exit_status_without_matches() {
+    let output = run_cli("no-match", &["src/tests/data/assignment.json^json/missing"]);
+    assert_eq!(output.status.code(), Some(1), "CLI exited with: {output:?}");
+    assert_eq!(stdout(&output), "");
+
+    // programs without path statements succeed
+    let output = run_cli("no-query", &["$x := src/tests/data/assignment.json^json"]);
+    assert_eq!(output.status.code(), Some(0), "CLI exited with: {output:?}");
+}
+
+#[test]
+fn cli_exit_status_on_error() {
+    let output = run_cli("error", &["src/tests/data/missing.json^json/a"]);
+    assert_eq!(output.status.code(), Some(2), "CLI exited with: {output:?}");
+
+    let output = run_cli(
+        "bad-flag",
+        &["--limit", "x", "src/tests/data/assignment.json"],
+    );
+    assert_eq!(output.status.code(), Some(2), "CLI exited with: {output:?}");
+
+    let output = run_cli("no-program", &[]);
+    assert_eq!(output.status.code(), Some(2), "CLI exited with: {output:?}");
+}
+
+#[test]
+fn cli_match_count_and_limit() {
+    let program = "src^fs/*";
+    let all = run_cli("count", &["--count", program]);
+    assert_eq!(all.status.code(), Some(0), "CLI exited with: {all:?}");
+    let count: usize = stdout(&all).trim().parse().expect("count is not a number");
+    assert!(count > 2, "expected several matches, got {count}");
+
+    let limited = run_cli("count-limit", &["--count", "--limit", "2", program]);
+    assert_eq!(stdout(&limited), "2\n");
+
+    let first = run_cli("first", &["--first", "--output", "paths", program]);
+    assert_eq!(first.status.code(), Some(0), "CLI exited with: {first:?}");
+    assert_eq!(stdout(&first).lines().count(), 1);
+
+    let none = run_cli(
+        "count-none",
+        &["--count", "src/tests/data/assignment.json^json/missing"],
+    );
+    assert_eq!(none.status.code(), Some(1), "CLI exited with: {none:?}");
+    assert_eq!(stdout(&none), "0\n");
 }
@@ -0,0 +1,126 @@
+use std::process::Command;
+
+fn run_cli(home: &std::path::Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hiallib"))
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .expect("failed to run CLI binary");
+    assert!(output.status.success(), "CLI exited with: {output:?}");
+    String::from_utf8(output.stdout).expect("CLI output is not utf8")
+}
+
+#[test]
+fn cli_output_formats() {
+    let temp_home =
+        std::env::temp_dir().join(format!("hial-cli-output-home-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_home).expect("failed to create temp home");
+    let file = temp_home.join("services.yaml");
+    std::fs::write(
+        &file,
+        "services:\n  api:\n    image: api:v1\n    ports: [80, 443]\n  db:\n    image: pg\n",
+    )
+    .expect("failed to write services.yaml");
+    let path = |p: &str| format!("{}^yaml/services{}", file.display(), p);
+
+    let json = run_cli(&temp_home, &["--output", "json", &path("/api")]);
+    assert_eq!(
+        json,
+        "{\n  \"image\": \"api:v1\",\n  \"ports\": [\n    80,\n    443\n  ]\n}\n"
+    );
+
+    let ndjson = run_cli(&temp_home, &["--output", "ndjson", &path("/*")]);
+    assert_eq!(
+        ndjson,
+        "{\"image\":\"api:v1\",\"ports\":[80,443]}\n{\"image\":\"pg\"}\n"
+    );
+
+    let yaml = run_cli(&temp_home, &["--output", "yaml", &path("/*/image")]);
+    assert_eq!(yaml, "---\napi:v1\n---\npg\n");
+
+    let values = run_cli(&temp_home, &["--output", "values", &path("/**/image")]);
+    assert_eq!(values, "api:v1\npg\n");
+
+    let paths = run_cli(&temp_home, &["--output", "paths", &path("/db/image")]);
+    assert!(
+        paths.trim_end().ends_with("^yaml/services/db/image"),
+        "{paths}"
+    );
+    assert_eq!(paths.lines().count(), 1);
+
+    // file contents are bytes, printed as strings
+    let file_json = run_cli(
+        &temp_home,
+        &["--output", "json", &format!("{}^fs", file.display())],
+    );
+    assert_eq!(
+        file_json,
+        "\"services:\\n  api:\\n    image: api:v1\\n    ports: [80, 443]\\n  db:\\n    image: pg\\n\"\n"
+    );
+
+    let _ = std::fs::remove_dir_all(&temp_home);
+}
+
+#[test]
+fn cli_program_file_and_stdin() {
+    use std::io::Write;
+
+    let temp_home =
+        std::env::temp_dir().join(format!("hial-cli-stdin-home-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_home).expect("failed to create temp home");
+    let script = temp_home.join("ids.hial");
+    std::fs::write(
+        &script,
+        "# print the ids\n$items := -^json/items  # from stdin\n\n$items/*/id\n",
+    )
+    .expect("failed to write ids.hial");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hiallib"))
+        .args(["--output", "values", "-f", script.to_str().unwrap()])
+        .env("HOME", &temp_home)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("failed to run CLI binary");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(br#"{"items": [{"id": 1}, {"id": "b"}]}"#)
+        .expect("failed to write stdin");
+    let output = child.wait_with_output().expect("failed to wait for CLI");
+    assert!(output.status.success(), "CLI exited with: {output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\nb\n");
+
+    let _ = std::fs::remove_dir_all(&temp_home);
+}
+
+#[test]
+fn cli_dry_run_preview() {
+    let temp_home =
+        std::env::temp_dir().join(format!("hial-cli-dry-run-home-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_home).expect("failed to create temp home");
+    let text = temp_home.join("a.txt");
+    let yaml = temp_home.join("c.yaml");
+    std::fs::write(&text, "old\n").expect("failed to write a.txt");
+    std::fs::write(&yaml, "x: 1\n").expect("failed to write c.yaml");
+
+    let program = format!(
+        "{}^fs[w] = 'new'; delete {}^fs[w]",
+        text.display(),
+        yaml.display()
+    );
+    let output = run_cli(&temp_home, &["--dry-run", &program]);
+    assert!(
+        output.contains(&format!("would write {}:", text.display())),
+        "{output}"
+    );
+    assert!(
+        output.contains(&format!("would delete {}", yaml.display())),
+        "{output}"
+    );
+    assert_eq!(std::fs::read_to_string(&text).unwrap(), "old\n");
+    assert_eq!(std::fs::read_to_string(&yaml).unwrap(), "x: 1\n");
+
+    let _ = std::fs::remove_dir_all(&temp_home);
+}